@@ -0,0 +1,404 @@
+//! reading of the source files from a tar or zip archive, with `--archive`
+
+use crate::inflate::{crc32, inflate};
+use crate::loader::{children, normalize};
+use crate::{DiskLoader, Error, SourceLoader};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const BLOCK_SIZE: usize = 512;
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER: &[u8] = b"PK\x01\x02";
+const ZIP_END: &[u8] = b"PK\x05\x06";
+/// the most bytes a compressed archive is decompressed to, a small archive can expand to a lot of data
+const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+/// loads the files from a `.tar`, `.tar.gz` or `.zip` archive, falling back to the disk
+///
+/// a path is matched by the entry name of the file relative to `base`,
+/// so `./src/lib/common.sh` is the `lib/common.sh` entry when `base` is `./src`
+pub struct ArchiveLoader {
+    archive: PathBuf,
+    base: PathBuf,
+    entries: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl ArchiveLoader {
+    /// read the entries of the archive, the kind of archive is taken from its first bytes
+    pub fn open(archive: &Path, base: &Path) -> Result<ArchiveLoader, Error> {
+        let contents = DiskLoader.load(archive)?;
+        let entries = if contents.starts_with(GZIP_MAGIC) {
+            gunzip(&contents, MAX_DECOMPRESSED_SIZE).and_then(|x| read_tar(&x))
+        } else if contents.starts_with(ZIP_MAGIC) {
+            read_zip(&contents, MAX_DECOMPRESSED_SIZE)
+        } else {
+            read_tar(&contents)
+        };
+
+        let entries = entries.map_err(|message| Error::Archive {
+            path: archive.to_path_buf(),
+            message,
+        })?;
+        Ok(ArchiveLoader {
+            archive: DiskLoader.canonicalize(archive)?,
+            base: normalize(base),
+            entries,
+        })
+    }
+
+    /// the entry name of the path, if the archive contains it
+    fn entry(&self, path: &Path) -> Option<&Path> {
+        let name = normalize(normalize(path).strip_prefix(&self.base).ok()?);
+        self.entries
+            .get_key_value(&name)
+            .map(|(key, _)| key.as_path())
+    }
+
+    /// the entries directly in the directory, the directories of the archive are the parents of its files
+    fn entries_in(&self, directory: &Path) -> Vec<PathBuf> {
+        children(self.entries.keys().map(|x| self.base.join(x)), directory)
+    }
+}
+
+impl SourceLoader for ArchiveLoader {
+    fn load(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self.entry(path) {
+            Some(name) => Ok(self.entries[name].clone()),
+            None => DiskLoader.load(path),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.entry(path).is_some() || self.is_dir(path) || DiskLoader.exists(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.entry(path).is_none() && (!self.entries_in(path).is_empty() || DiskLoader.is_dir(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut paths = self.entries_in(path);
+        if DiskLoader.is_dir(path) {
+            paths.extend(DiskLoader.read_dir(path)?);
+            paths.sort();
+            paths.dedup();
+        }
+        Ok(paths)
+    }
+
+    fn size(&self, path: &Path) -> Option<u64> {
+        match self.entry(path) {
+            Some(name) => Some(self.entries[name].len() as u64),
+            None => DiskLoader.size(path),
+        }
+    }
+
+    fn is_readable(&self, path: &Path) -> bool {
+        self.entry(path).is_some() || DiskLoader.is_readable(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        match self.entry(path) {
+            Some(name) => Ok(self.archive.join(name)),
+            // a directory that is only in the archive
+            None if !DiskLoader.exists(path) && self.is_dir(path) => Ok(self
+                .archive
+                .join(normalize(path).strip_prefix(&self.base).unwrap_or(path))),
+            None => DiskLoader.canonicalize(path),
+        }
+    }
+}
+
+/// the decompressed contents of a gzip compressed file, of at most `limit` bytes
+fn gunzip(contents: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if contents.get(2) != Some(&8) {
+        return Err(String::from("unsupported gzip compression method"));
+    }
+    let flags = *contents.get(3).ok_or("unexpected end of the archive")?;
+
+    let mut offset = 10;
+    // the optional extra field, file name, comment and header checksum
+    if flags & 0x04 != 0 {
+        offset += 2 + number(contents, offset, 2)?;
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let end = contents
+                .get(offset..)
+                .and_then(|x| x.iter().position(|x| *x == 0))
+                .ok_or("unexpected end of the archive")?;
+            offset += end + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        offset += 2;
+    }
+
+    let (data, used) = inflate(
+        contents
+            .get(offset..)
+            .ok_or("unexpected end of the archive")?,
+        limit,
+    )?;
+    let offset = offset + used;
+    if number(contents, offset, 4)? as u32 != crc32(&data)
+        || number(contents, offset + 4, 4)? as u32 != data.len() as u32
+    {
+        return Err(String::from(
+            "the decompressed data does not match the checksum",
+        ));
+    }
+    Ok(data)
+}
+
+/// the files of a zip archive by their normalized entry name, from its central directory
+///
+/// the files together are at most `limit` bytes
+fn read_zip(contents: &[u8], limit: usize) -> Result<HashMap<PathBuf, Vec<u8>>, String> {
+    let end = contents
+        .windows(ZIP_END.len())
+        .rposition(|x| x == ZIP_END)
+        .ok_or("missing the end of the central directory")?;
+    let count = number(contents, end + 10, 2)?;
+    let mut offset = number(contents, end + 16, 4)?;
+
+    let mut entries = HashMap::new();
+    let mut left = limit;
+    for _ in 0..count {
+        if contents.get(offset..offset + 4) != Some(ZIP_CENTRAL_HEADER) {
+            return Err(String::from("invalid central directory entry"));
+        }
+        let method = number(contents, offset + 10, 2)?;
+        let crc = number(contents, offset + 16, 4)? as u32;
+        let size = number(contents, offset + 20, 4)?;
+        let uncompressed_size = number(contents, offset + 24, 4)?;
+        let name_len = number(contents, offset + 28, 2)?;
+        let local = number(contents, offset + 42, 4)?;
+        let name = contents
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or("unexpected end of the archive")?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        offset +=
+            46 + name_len + number(contents, offset + 30, 2)? + number(contents, offset + 32, 2)?;
+
+        // directories end with a slash
+        if name.ends_with('/') {
+            continue;
+        }
+        if uncompressed_size > left {
+            return Err(format!(
+                "the decompressed data is larger than {} bytes",
+                limit
+            ));
+        }
+        if contents.get(local..local + 4) != Some(ZIP_MAGIC) {
+            return Err(format!("invalid local header of {}", name));
+        }
+        let start =
+            local + 30 + number(contents, local + 26, 2)? + number(contents, local + 28, 2)?;
+        let data = contents
+            .get(start..start + size)
+            .ok_or("unexpected end of the archive")?;
+        let data = match method {
+            0 => data.to_vec(),
+            8 => inflate(data, uncompressed_size)?.0,
+            _ => {
+                return Err(format!(
+                    "unsupported compression method {} of {}",
+                    method, name
+                ))
+            }
+        };
+        if data.len() != uncompressed_size || crc32(&data) != crc {
+            return Err(format!(
+                "the contents of {} do not match the checksum",
+                name
+            ));
+        }
+        left -= data.len();
+        entries.insert(normalize(Path::new(&name)), data);
+    }
+
+    Ok(entries)
+}
+
+/// a little endian number of `len` bytes at the offset
+fn number(contents: &[u8], offset: usize, len: usize) -> Result<usize, String> {
+    let bytes = contents
+        .get(offset..offset + len)
+        .ok_or("unexpected end of the archive")?;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0, |x, byte| x << 8 | *byte as usize))
+}
+
+/// the regular files of a ustar or GNU tar archive by their normalized entry name
+fn read_tar(contents: &[u8]) -> Result<HashMap<PathBuf, Vec<u8>>, String> {
+    let mut entries = HashMap::new();
+    let mut long_name = None;
+    let mut offset = 0;
+
+    while let Some(header) = contents.get(offset..offset + BLOCK_SIZE) {
+        // the archive ends with zeroed blocks
+        if header.iter().all(|x| *x == 0) {
+            break;
+        }
+
+        let size = octal(&header[124..136]).ok_or("invalid entry size")?;
+        let start = offset + BLOCK_SIZE;
+        let data = contents
+            .get(start..start + size)
+            .ok_or("unexpected end of the archive")?;
+        offset = start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+
+        let name = match long_name.take() {
+            Some(name) => name,
+            None => header_name(header),
+        };
+        match header[156] {
+            // GNU long name, used for the name of the next entry
+            b'L' => long_name = Some(field(data)),
+            b'0' | 0 => {
+                entries.insert(normalize(Path::new(&name)), data.to_vec());
+            }
+            _ => (),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// the name of the entry, including the ustar prefix
+fn header_name(header: &[u8]) -> String {
+    let name = field(&header[0..100]);
+    let prefix = if &header[257..262] == b"ustar" {
+        field(&header[345..500])
+    } else {
+        String::new()
+    };
+
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// a nul terminated text field
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|x| *x == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// a nul or space terminated octal number field
+fn octal(bytes: &[u8]) -> Option<usize> {
+    let text = field(bytes);
+    let text = text.trim_matches(|x: char| x == ' ' || x == '\0');
+    if text.is_empty() {
+        return Some(0);
+    }
+    usize::from_str_radix(text, 8).ok()
+}
+
+#[test]
+fn reads_tar_entries() {
+    let entries = read_tar(&std::fs::read("./tests/fragments.tar").unwrap()).unwrap();
+
+    assert_eq!(
+        b"common() {\n    more\n}\n# import ./more.sh\n".to_vec(),
+        entries[Path::new("lib/common.sh")]
+    );
+    assert!(entries.contains_key(Path::new("lib/more.sh")));
+    assert!(!entries.contains_key(Path::new("lib")));
+}
+
+#[test]
+fn reads_compressed_archives() {
+    let expected = read_tar(&std::fs::read("./tests/fragments.tar").unwrap()).unwrap();
+
+    let tar = gunzip(&std::fs::read("./tests/fragments.tar.gz").unwrap(), 1 << 20).unwrap();
+    assert_eq!(expected, read_tar(&tar).unwrap());
+    assert_eq!(
+        expected,
+        read_zip(&std::fs::read("./tests/fragments.zip").unwrap(), 1 << 20).unwrap()
+    );
+}
+
+#[test]
+fn reads_gnu_long_names() {
+    let entries = read_tar(&std::fs::read("./tests/long_name.tar").unwrap()).unwrap();
+    let name = format!("lib/{}/utils.sh", "very_long_directory_name_".repeat(4));
+
+    assert_eq!(1, entries.len());
+    assert_eq!(
+        b"utils() {\n    echo utils\n}\n".to_vec(),
+        entries[Path::new(&name)]
+    );
+}
+
+#[test]
+fn rejects_oversized_archives() {
+    // the tar is 10240 bytes, the files in the zip 68 bytes
+    let gzip = std::fs::read("./tests/fragments.tar.gz").unwrap();
+    assert_eq!(
+        Err(String::from(
+            "the decompressed data is larger than 10000 bytes"
+        )),
+        gunzip(&gzip, 10000)
+    );
+    let zip = std::fs::read("./tests/fragments.zip").unwrap();
+    assert_eq!(
+        Err(String::from(
+            "the decompressed data is larger than 60 bytes"
+        )),
+        read_zip(&zip, 60)
+    );
+}
+
+#[test]
+fn rejects_malformed_archives() {
+    let gzip = std::fs::read("./tests/fragments.tar.gz").unwrap();
+    let mut wrong_method = gzip.clone();
+    wrong_method[2] = 0;
+    assert_eq!(
+        Err(String::from("unsupported gzip compression method")),
+        gunzip(&wrong_method, 1 << 20)
+    );
+    assert!(gunzip(&gzip[..gzip.len() / 2], 1 << 20).is_err());
+    // the CRC-32 is the first field of the trailer
+    let mut wrong_crc = gzip.clone();
+    wrong_crc[gzip.len() - 8] ^= 0xff;
+    assert_eq!(
+        Err(String::from(
+            "the decompressed data does not match the checksum"
+        )),
+        gunzip(&wrong_crc, 1 << 20)
+    );
+
+    let zip = std::fs::read("./tests/fragments.zip").unwrap();
+    let end = zip.len() - 22;
+    assert_eq!(
+        Err(String::from("missing the end of the central directory")),
+        read_zip(&zip[..end], 1 << 20)
+    );
+    // the central directory starts at the offset in its end record
+    let central = number(&zip, end + 16, 4).unwrap();
+    let mut truncated = zip[..central + 10].to_vec();
+    truncated.extend_from_slice(&zip[end..]);
+    assert!(read_zip(&truncated, 1 << 20).is_err());
+    // the CRC-32 of the first file in the central directory
+    let mut wrong_crc = zip.clone();
+    let lengths: usize = [28, 30, 32]
+        .iter()
+        .map(|x| number(&zip, central + x, 2).unwrap())
+        .sum();
+    // the first entry is the `lib/` directory
+    let crc = central + 46 + lengths + 16;
+    wrong_crc[crc] ^= 0xff;
+    assert!(read_zip(&wrong_crc, 1 << 20)
+        .unwrap_err()
+        .ends_with("do not match the checksum"));
+}
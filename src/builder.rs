@@ -0,0 +1,199 @@
+//! builder for the arguments when using the bundler as a library
+
+use crate::{Args, Indent, LineEnding, Mode, PathStyle, RootOrder};
+use glob::Pattern;
+use std::path::PathBuf;
+
+/// generates a setter for each field, `some` fields are wrapped in `Some`
+macro_rules! setters {
+    (
+        set { $($(#[$set_doc:meta])* $set_field:ident: $set_type:ty,)* }
+        some { $($(#[$some_doc:meta])* $some_field:ident: $some_type:ty,)* }
+    ) => {
+        $(
+            $(#[$set_doc])*
+            pub fn $set_field(mut self, $set_field: $set_type) -> Self {
+                self.args.$set_field = $set_field;
+                self
+            }
+        )*
+        $(
+            $(#[$some_doc])*
+            pub fn $some_field(mut self, $some_field: $some_type) -> Self {
+                self.args.$some_field = Some($some_field);
+                self
+            }
+        )*
+    };
+}
+
+#[derive(Debug)]
+/// builds the `Args` for bundling a root file, fields that are not set keep their default
+///
+/// ```
+/// use bash_bundler::{ArgsBuilder, BashFile};
+///
+/// let args = ArgsBuilder::new("./tests/source.sh")
+///     .replace_source(true)
+///     .replace_comment(false)
+///     .build();
+/// let bundle = BashFile::resolve("./tests/source.sh".into(), &args).unwrap();
+/// ```
+pub struct ArgsBuilder {
+    args: Args,
+}
+
+impl ArgsBuilder {
+    /// start building the arguments for the root file
+    pub fn new<P: Into<PathBuf>>(root_path: P) -> Self {
+        ArgsBuilder {
+            args: Args {
+                root_path: Some(root_path.into()),
+                ..Args::default()
+            },
+        }
+    }
+
+    /// skip imports whose path or import text matches the pattern
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.args.exclude.push(pattern);
+        self
+    }
+
+    /// retry imports that are not found relative to this directory, after the ones added before
+    pub fn search_path<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.args.search_path.push(dir.into());
+        self
+    }
+
+    /// resolve imports starting with the `@name` alias relative to this path
+    pub fn alias<P: Into<PathBuf>>(mut self, name: &str, path: P) -> Self {
+        self.args.aliases.insert(String::from(name), path.into());
+        self
+    }
+
+    setters! {
+        set {
+            /// enable the `source ./file.sh` syntax
+            replace_source: bool,
+            /// enable the `# import ./file.sh` syntax, enabled by default
+            replace_comment: bool,
+            /// enable both syntaxes, failing if a `source` path is ambiguous
+            auto: bool,
+            /// the comment token used in the `# import ./file.sh` syntax
+            comment_prefix: String,
+            /// continue a `# import` path ending in `\` on the next comment line
+            import_continuation: bool,
+            /// inline the `# import? ./file.sh` imports
+            optional_imports: bool,
+            /// retry imports that are not found with a file name that only differs in case
+            ignore_case: bool,
+            /// resolve the `source ./file.sh` syntax relative to the current file
+            source_relative_to_file: bool,
+            /// line ending used in the output
+            line_ending: LineEnding,
+            /// remove the shebang line from the output
+            no_shebang: bool,
+            /// collapse consecutive blank lines into one
+            squeeze_blank: bool,
+            /// number of spaces per indentation level used by `reindent`
+            indent_width: usize,
+            /// replace invalid UTF-8 in the files with the replacement character
+            lossy: bool,
+            /// fail if the root file or an imported file is not readable
+            require_readable: bool,
+            /// fail instead of warning for unresolved and empty imports, imports outside `root_dir` and duplicate functions
+            strict: bool,
+            /// collect the errors of recoverable imports and report them all at the end
+            keep_going: bool,
+            /// do not expand environment variables in import paths
+            no_env: bool,
+            /// replace `\` with `/` in import paths
+            normalize_separators: bool,
+            /// do not end the output with a newline
+            quiet: bool,
+            /// also inline the files in subdirectories when importing a directory
+            recursive_dir: bool,
+            /// resolve the imports in the `prepend` and `append` files
+            resolve_injected: bool,
+            /// put exactly one blank line between inlined files
+            blank_between_files: bool,
+            /// remove the leading and trailing blank lines of every imported file
+            trim_imported: bool,
+            /// inline every import, or put every file once before the files importing it
+            mode: Mode,
+            /// where the root file goes relative to the libraries in flatten mode
+            root_order: RootOrder,
+            /// put this line between the files inlined into the root file
+            separator: String,
+            /// leave a comment before the contents of every resolved import
+            keep_import_markers: bool,
+            /// keep the import line as a comment before the contents it imports
+            keep_source_ref: bool,
+            /// keep the import line as is, with begin and end markers around its contents
+            keep_directives: bool,
+            /// show the resolved paths in the markers, relative to the current directory or absolute
+            path_style: PathStyle,
+            /// warn when a function is defined more than once in the output
+            normalize_functions: bool,
+            /// warn about bash only syntax in the inlined files of a `#!/bin/sh` root file
+            compat_lint: bool,
+            /// warn when the interpreter of the shebang line of the output is not installed
+            interpreter_check: bool,
+            /// only run the top level statements of the root file when the bundle is executed
+            main_guard: bool,
+            /// comment out the top level commands of the inlined files, keeping the definitions
+            strip_toplevel_from_imports: bool,
+        }
+        some {
+            /// resolve the `# import ./file.sh` syntax relative to this directory
+            import_base: PathBuf,
+            /// use this shebang line, it should start with `#!`
+            shebang: String,
+            /// append a call to this function at the end of the output
+            call_main: String,
+            /// pipe the contents of every file through this shell command before looking for imports
+            preprocess: String,
+            /// resolve imports to the files in this `.tar` or `.tar.gz` archive first
+            archive: PathBuf,
+            /// insert this text as comments after the shebang line
+            banner: String,
+            /// insert the contents of this file as comments after the shebang line
+            banner_file: PathBuf,
+            /// insert the contents of this file at the start of the output, after the shebang line
+            prepend: PathBuf,
+            /// insert the contents of this file at the end of the output
+            append: PathBuf,
+            /// normalize the leading whitespace to spaces or tabs
+            reindent: Indent,
+            /// warn when an import resolves to a file outside this directory
+            root_dir: PathBuf,
+            /// only inline imports up to this many levels deep
+            depth: usize,
+            /// fail when the output gets larger than this many bytes
+            max_output_bytes: usize,
+            /// fail when the root file or an imported file is larger than this many bytes
+            max_file_size: usize,
+        }
+    }
+
+    /// the configured arguments
+    pub fn build(self) -> Args {
+        self.args
+    }
+}
+
+#[test]
+fn builds_args() {
+    let args = ArgsBuilder::new("./tests/source.sh")
+        .replace_source(true)
+        .replace_comment(false)
+        .depth(2)
+        .build();
+
+    assert_eq!(Some(PathBuf::from("./tests/source.sh")), args.root_path);
+    assert!(args.replace_source);
+    assert!(!args.replace_comment);
+    assert_eq!(Some(2), args.depth);
+    assert_eq!("#", args.comment_prefix);
+}
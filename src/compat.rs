@@ -0,0 +1,108 @@
+//! heuristic lint for bash only syntax in files inlined into a `sh` script, with `--compat-lint`
+
+/// shells that do not support the bash syntax
+const POSIX_SHELLS: &[&str] = &["sh", "dash", "ash"];
+
+/// whether the shebang line runs the script with a POSIX shell, like `#!/bin/sh` or `#!/usr/bin/env sh`
+pub fn is_sh_shebang(line: &str) -> bool {
+    let mut words = match line.strip_prefix("#!") {
+        Some(rest) => rest.split_whitespace(),
+        None => return false,
+    };
+    let mut interpreter = words.next().unwrap_or_default();
+    if interpreter.ends_with("/env") {
+        interpreter = words.next().unwrap_or_default();
+    }
+    let name = interpreter.rsplit('/').next().unwrap_or_default();
+    POSIX_SHELLS.contains(&name)
+}
+
+/// a description of the bash only syntax used in the line, comments are skipped
+///
+/// this is a curated set of patterns, not a parser, so it can miss bashisms and match quoted text
+pub fn bashism(line: &str) -> Option<&'static str> {
+    let line = line.trim_start();
+    if line.starts_with('#') {
+        return None;
+    }
+
+    let mut words = line.split_whitespace();
+    let first = words.next()?;
+    if first == "function" {
+        return Some("the `function` keyword");
+    }
+    if first == "source" {
+        return Some("`source`, use `.` instead");
+    }
+    if is_array_assignment(first) {
+        return Some("an array assignment");
+    }
+    if first == "[[" || words.any(|x| x == "[[") {
+        return Some("a `[[ ]]` test");
+    }
+    if line.contains("<<<") {
+        return Some("a `<<<` here-string");
+    }
+    if line.contains("&>") {
+        return Some("a `&>` redirection");
+    }
+    if has_array_index(line) {
+        return Some("an array index `${name[...]}`");
+    }
+    None
+}
+
+/// `name=(...)`, a `$(` command substitution is not an array
+fn is_array_assignment(word: &str) -> bool {
+    let name = word
+        .split_once("=(")
+        .map(|(name, _)| name.strip_suffix('+').unwrap_or(name));
+    name.is_some_and(is_name)
+}
+
+/// `${name[`, the index of an array
+fn has_array_index(line: &str) -> bool {
+    line.match_indices("${").any(|(index, _)| {
+        let rest = &line[index + 2..];
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        end > 0 && rest[end..].starts_with('[')
+    })
+}
+
+/// a valid shell variable name
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[test]
+fn sh_shebangs() {
+    assert!(is_sh_shebang("#!/bin/sh"));
+    assert!(is_sh_shebang("#!/bin/sh -e"));
+    assert!(is_sh_shebang("#!/usr/bin/env sh"));
+    assert!(is_sh_shebang("#! /bin/dash"));
+    assert!(!is_sh_shebang("#!/bin/bash"));
+    assert!(!is_sh_shebang("#!/usr/bin/env bash"));
+    assert!(!is_sh_shebang("echo /bin/sh"));
+}
+
+#[test]
+fn finds_bashisms() {
+    assert!(bashism("if [[ -n $1 ]]; then").is_some());
+    assert!(bashism("function greet {").is_some());
+    assert!(bashism("names=(a b c)").is_some());
+    assert!(bashism("names+=(d)").is_some());
+    assert!(bashism("echo \"${names[0]}\"").is_some());
+    assert!(bashism("read -r line <<< \"$input\"").is_some());
+    assert!(bashism("command &> /dev/null").is_some());
+    assert!(bashism("source ./utils.sh").is_some());
+
+    assert_eq!(None, bashism("if [ -n \"$1\" ]; then"));
+    assert_eq!(None, bashism("output=$(command)"));
+    assert_eq!(None, bashism("echo \"${name}\" > /dev/null 2>&1"));
+    assert_eq!(None, bashism("# [[ in a comment ]]"));
+    assert_eq!(None, bashism(""));
+}
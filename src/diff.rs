@@ -0,0 +1,306 @@
+//! line based diff between two bundles
+
+use crate::source_map::{self, SourceSpan};
+use std::path::PathBuf;
+
+const CONTEXT: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum Change<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// diff the lines of the two inputs using the longest common subsequence
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Change<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut changes: Vec<Change> = old[..prefix].iter().map(|x| Change::Equal(x)).collect();
+    diff_middle(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+        &mut changes,
+    );
+    changes.extend(old[old.len() - suffix..].iter().map(|x| Change::Equal(x)));
+
+    // the deleted lines of a changed block go before the inserted lines
+    let mut start = 0;
+    while start < changes.len() {
+        let length = changes[start..]
+            .iter()
+            .take_while(|x| !matches!(x, Change::Equal(_)))
+            .count();
+        changes[start..start + length].sort_by_key(|x| matches!(x, Change::Insert(_)));
+        start += length.max(1);
+    }
+
+    changes
+}
+
+/// the changes between the lines, split in halves in linear space (Hirschberg's algorithm)
+fn diff_middle<'a>(old: &[&'a str], new: &[&'a str], changes: &mut Vec<Change<'a>>) {
+    match old {
+        [] => changes.extend(new.iter().map(|x| Change::Insert(x))),
+        _ if new.is_empty() => changes.extend(old.iter().map(|x| Change::Delete(x))),
+        [line] => match new.iter().position(|x| x == line) {
+            Some(index) => {
+                changes.extend(new[..index].iter().map(|x| Change::Insert(x)));
+                changes.push(Change::Equal(line));
+                changes.extend(new[index + 1..].iter().map(|x| Change::Insert(x)));
+            }
+            None => {
+                changes.push(Change::Delete(line));
+                changes.extend(new.iter().map(|x| Change::Insert(x)));
+            }
+        },
+        _ => {
+            let middle = old.len() / 2;
+            let forward = lcs_lengths(old[..middle].iter(), new.iter());
+            let backward = lcs_lengths(old[middle..].iter().rev(), new.iter().rev());
+            // split the new lines where the common subsequences of both halves are the longest
+            let split = (0..=new.len())
+                .max_by_key(|&j| (forward[j] + backward[new.len() - j], std::cmp::Reverse(j)))
+                .unwrap_or_default();
+            diff_middle(&old[..middle], &new[..split], changes);
+            diff_middle(&old[middle..], &new[split..], changes);
+        }
+    }
+}
+
+/// the lengths of the longest common subsequence of the old lines and every prefix of the new lines
+fn lcs_lengths<'a, 'b>(
+    old: impl Iterator<Item = &'a &'b str>,
+    new: impl Iterator<Item = &'a &'b str> + Clone,
+) -> Vec<usize>
+where
+    'b: 'a,
+{
+    let mut lengths = vec![0; new.clone().count() + 1];
+    for a in old {
+        let mut diagonal = 0;
+        for (j, b) in new.clone().enumerate() {
+            let above = lengths[j + 1];
+            lengths[j + 1] = if a == b {
+                diagonal + 1
+            } else {
+                above.max(lengths[j])
+            };
+            diagonal = above;
+        }
+    }
+    lengths
+}
+
+/// the source spans of a bundle made with `--keep-directives`,
+/// from its `# (begin ./file.sh)` and `# (end ./file.sh)` markers
+///
+/// the lines outside of the markers come from the root file, which the bundle does not name
+pub fn marker_spans(contents: &str, comment_prefix: &str) -> Vec<SourceSpan> {
+    let mut spans = Vec::new();
+    // the file of every open marker, with the number of its lines so far
+    let mut open: Vec<(PathBuf, usize)> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let marker = line
+            .trim_start()
+            .strip_prefix(comment_prefix)
+            .and_then(|x| x.strip_prefix(" ("))
+            .and_then(|x| x.strip_suffix(')'));
+        if let Some(path) = marker.and_then(|x| x.strip_prefix("begin ")) {
+            open.push((PathBuf::from(path), 0));
+            continue;
+        }
+        if marker.is_some_and(|x| x.starts_with("end ")) && open.pop().is_some() {
+            continue;
+        }
+        if let Some((path, count)) = open.last_mut() {
+            source_map::push_line(&mut spans, index, path, *count);
+            *count += 1;
+        }
+    }
+    spans
+}
+
+/// format the difference between the two inputs as a unified diff
+///
+/// a hunk is labeled with the source file and line of its first changed line, found in the spans
+/// of the side it is on, hunks in lines without a span are not labeled
+///
+/// returns an empty string if the inputs have the same lines
+pub fn unified(
+    old_name: &str,
+    old: &str,
+    old_spans: &[SourceSpan],
+    new_name: &str,
+    new: &str,
+    new_spans: &[SourceSpan],
+) -> String {
+    let changes = diff_lines(old, new);
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (index, change) in changes.iter().enumerate() {
+        if let Change::Equal(_) = change {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(changes.len());
+        match ranges.last_mut() {
+            Some(last) if last.1 >= start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    if ranges.is_empty() {
+        return String::new();
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let (mut old_line, mut new_line, mut position) = (1, 1, 0);
+    for (start, end) in ranges {
+        for change in &changes[position..start] {
+            match change {
+                Change::Equal(_) => {
+                    old_line += 1;
+                    new_line += 1;
+                }
+                Change::Delete(_) => old_line += 1,
+                Change::Insert(_) => new_line += 1,
+            }
+        }
+
+        let hunk = &changes[start..end];
+        let old_count = hunk
+            .iter()
+            .filter(|x| !matches!(x, Change::Insert(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|x| !matches!(x, Change::Delete(_)))
+            .count();
+        let source = first_change(hunk, old_line - 1, new_line - 1).and_then(|x| match x {
+            (Change::Delete(_), index) => source_map::find(old_spans, index),
+            (_, index) => source_map::find(new_spans, index),
+        });
+        let label = match source {
+            Some((path, line)) => format!(" {}:{}", path.display(), line + 1),
+            None => String::new(),
+        };
+        output.push_str(&format!(
+            "@@ -{},{} +{},{} @@{}\n",
+            old_line, old_count, new_line, new_count, label
+        ));
+        for change in hunk {
+            match change {
+                Change::Equal(line) => output.push_str(&format!(" {}\n", line)),
+                Change::Delete(line) => output.push_str(&format!("-{}\n", line)),
+                Change::Insert(line) => output.push_str(&format!("+{}\n", line)),
+            }
+        }
+
+        old_line += old_count;
+        new_line += new_count;
+        position = end;
+    }
+
+    output
+}
+
+/// the first change in the hunk that is not an equal line, with its line in the old or new input
+fn first_change<'a, 'b>(
+    hunk: &'a [Change<'b>],
+    mut old_line: usize,
+    mut new_line: usize,
+) -> Option<(&'a Change<'b>, usize)> {
+    for change in hunk {
+        match change {
+            Change::Equal(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Change::Delete(_) => return Some((change, old_line)),
+            Change::Insert(_) => return Some((change, new_line)),
+        }
+    }
+    None
+}
+
+#[test]
+fn diff_lines_changed_line() {
+    let changes = diff_lines("a\nb\nc\n", "a\nx\nc\n");
+
+    let expected = vec![
+        Change::Equal("a"),
+        Change::Delete("b"),
+        Change::Insert("x"),
+        Change::Equal("c"),
+    ];
+
+    assert_eq!(expected, changes)
+}
+
+#[test]
+fn diff_lines_moved_lines() {
+    let changes = diff_lines("a\nb\nc\nd\ne\n", "b\nc\na\ne\nx\n");
+
+    let old: Vec<&str> = changes
+        .iter()
+        .filter_map(|x| match x {
+            Change::Equal(line) | Change::Delete(line) => Some(*line),
+            Change::Insert(_) => None,
+        })
+        .collect();
+    let new: Vec<&str> = changes
+        .iter()
+        .filter_map(|x| match x {
+            Change::Equal(line) | Change::Insert(line) => Some(*line),
+            Change::Delete(_) => None,
+        })
+        .collect();
+    let equal = changes
+        .iter()
+        .filter(|x| matches!(x, Change::Equal(_)))
+        .count();
+
+    assert_eq!(vec!["a", "b", "c", "d", "e"], old);
+    assert_eq!(vec!["b", "c", "a", "e", "x"], new);
+    assert_eq!(3, equal);
+}
+
+#[test]
+fn unified_equal_is_empty() {
+    assert_eq!("", unified("old", "a\nb\n", &[], "new", "a\nb\n", &[]))
+}
+
+#[test]
+fn spans_from_markers() {
+    let bundle = "# import ./a.sh\n# (begin ./a.sh)\na\n# import ./b.sh\n# (begin ./b.sh)\nb\n# (end ./b.sh)\nc\n# (end ./a.sh)\nmain\n";
+    let spans = marker_spans(bundle, "#");
+
+    assert_eq!(None, source_map::find(&spans, 0));
+    assert_eq!(
+        Some((std::path::Path::new("./a.sh"), 0)),
+        source_map::find(&spans, 2)
+    );
+    assert_eq!(
+        Some((std::path::Path::new("./b.sh"), 0)),
+        source_map::find(&spans, 5)
+    );
+    assert_eq!(
+        Some((std::path::Path::new("./a.sh"), 2)),
+        source_map::find(&spans, 7)
+    );
+    assert_eq!(None, source_map::find(&spans, 9));
+}
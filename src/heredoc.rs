@@ -0,0 +1,140 @@
+//! detection of heredoc bodies and multiline strings, where whitespace and import-like lines are significant
+
+#[derive(Debug, Default)]
+/// tracks whether lines start inside a heredoc or a multiline quoted string
+pub struct Literal {
+    heredoc: Heredoc,
+    quote: Option<char>,
+}
+
+impl Literal {
+    pub fn new() -> Self {
+        Literal::default()
+    }
+
+    /// feed the next line, returns true if the line starts inside a heredoc body or a quoted string
+    pub fn is_literal(&mut self, line: &str) -> bool {
+        let quoted = self.quote.is_some();
+        if !quoted && self.heredoc.is_body(line) {
+            return true;
+        }
+        self.quote = scan_quotes(line, self.quote);
+        quoted
+    }
+}
+
+/// returns the quote that is still open at the end of the line
+fn scan_quotes(line: &str, mut open: Option<char>) -> Option<char> {
+    let mut chars = line.chars();
+    let mut previous_whitespace = true;
+    while let Some(c) = chars.next() {
+        match (open, c) {
+            (Some('\''), '\'') => open = None,
+            (Some('\''), _) => (),
+            (_, '\\') => {
+                chars.next();
+            }
+            (Some(_), '"') => open = None,
+            (Some(_), _) => (),
+            (None, '\'') | (None, '"') => open = Some(c),
+            (None, '#') if previous_whitespace => break,
+            (None, _) => (),
+        }
+        previous_whitespace = c.is_whitespace();
+    }
+    open
+}
+
+#[derive(Debug, Default)]
+/// tracks whether lines are inside a heredoc while iterating over a file
+pub struct Heredoc {
+    delimiter: Option<String>,
+    strip_tabs: bool,
+}
+
+impl Heredoc {
+    pub fn new() -> Self {
+        Heredoc::default()
+    }
+
+    /// feed the next line, returns true if the line belongs to a heredoc body (including the closing delimiter)
+    pub fn is_body(&mut self, line: &str) -> bool {
+        if let Some(delimiter) = &self.delimiter {
+            let line = if self.strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                line
+            };
+            if line == delimiter {
+                self.delimiter = None;
+            }
+            return true;
+        }
+
+        if let Some((delimiter, strip_tabs)) = start_delimiter(line) {
+            self.delimiter = Some(delimiter);
+            self.strip_tabs = strip_tabs;
+        }
+        false
+    }
+}
+
+/// finds the delimiter of a heredoc started on this line, `<<<` herestrings are skipped
+fn start_delimiter(line: &str) -> Option<(String, bool)> {
+    if line.trim_start().starts_with('#') {
+        return None;
+    }
+
+    let mut rest = line;
+    while let Some(index) = rest.find("<<") {
+        let after = &rest[index + 2..];
+        if let Some(after) = after.strip_prefix('<') {
+            rest = after.trim_start_matches('<');
+            continue;
+        }
+
+        let (after, strip_tabs) = match after.strip_prefix('-') {
+            Some(x) => (x, true),
+            None => (after, false),
+        };
+        let after = after.trim_start();
+
+        let delimiter: String = match after.chars().next() {
+            Some(quote @ '\'') | Some(quote @ '"') => after[1..].split(quote).next()?.to_string(),
+            _ => after
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '\\')
+                .filter(|c| *c != '\\')
+                .collect(),
+        };
+
+        if delimiter.is_empty() {
+            return None;
+        }
+        return Some((delimiter, strip_tabs));
+    }
+
+    None
+}
+
+#[test]
+fn heredoc_body() {
+    let input = "cat <<EOF\nhallo\n\nEOF\necho done\ncat <<-'END' > file\n\tx\n\tEND\ncat <<< \"no heredoc\"";
+    let mut heredoc = Heredoc::new();
+    let body: Vec<bool> = input.lines().map(|x| heredoc.is_body(x)).collect();
+
+    let expected = vec![false, true, true, true, false, false, true, true, false];
+
+    assert_eq!(expected, body)
+}
+
+#[test]
+fn literal_lines() {
+    let input = "msg=\"\n# import ./x.sh\n\"\necho 'it''s' # don't\ncat <<EOF\n# import ./x.sh\nEOF\n# import ./x.sh";
+    let mut literal = Literal::new();
+    let lines: Vec<bool> = input.lines().map(|x| literal.is_literal(x)).collect();
+
+    let expected = vec![false, true, true, false, false, true, true, false];
+
+    assert_eq!(expected, lines)
+}
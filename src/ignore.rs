@@ -0,0 +1,157 @@
+//! the `.bashbundlerignore` file, excluding files from glob and directory imports
+
+use crate::loader::normalize;
+use crate::Error;
+use glob::{MatchOptions, Pattern};
+use std::path::{Component, Path, PathBuf};
+
+/// name of the ignore file, read from the directory of the root file
+pub const IGNORE_FILE: &str = ".bashbundlerignore";
+
+#[derive(Debug)]
+struct IgnorePattern {
+    pattern: Pattern,
+    /// a `!` pattern includes the files ignored by the lines before it again
+    negated: bool,
+    /// a pattern ending in `/` only matches directories
+    dir_only: bool,
+}
+
+#[derive(Debug, Default)]
+/// gitignore style patterns, relative to the directory of the ignore file
+pub struct IgnoreFile {
+    root: PathBuf,
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreFile {
+    /// read the ignore file in the directory, without the file nothing is ignored
+    pub fn load(root: &Path) -> Result<IgnoreFile, Error> {
+        let path = root.join(IGNORE_FILE);
+        if !path.is_file() {
+            return Ok(IgnoreFile::default());
+        }
+        let contents = std::fs::read_to_string(&path).map_err(|source| Error::IoWithPath {
+            path: path.clone(),
+            source,
+        })?;
+        IgnoreFile::parse(root, &contents).map_err(|(pattern, message)| Error::Glob {
+            pattern: PathBuf::from(pattern),
+            message,
+        })
+    }
+
+    /// the patterns, one per line, blank lines and `#` comments are skipped
+    fn parse(root: &Path, contents: &str) -> Result<IgnoreFile, (String, String)> {
+        let mut patterns = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            // a pattern without a `/` matches at any depth, like in a `.gitignore`
+            let pattern = match line.strip_prefix('/') {
+                Some(line) => String::from(line),
+                None if line.contains('/') => String::from(line),
+                None => format!("**/{}", line),
+            };
+            let pattern =
+                Pattern::new(&pattern).map_err(|err| (String::from(line), err.to_string()))?;
+            patterns.push(IgnorePattern {
+                pattern,
+                negated,
+                dir_only,
+            });
+        }
+
+        Ok(IgnoreFile {
+            root: root.to_path_buf(),
+            patterns,
+        })
+    }
+
+    /// whether the file or directory, or a directory it is in, is ignored
+    ///
+    /// paths outside the directory of the ignore file are never ignored
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let relative = match self.relative(path) {
+            Some(relative) => relative,
+            None => return false,
+        };
+
+        let mut directories: Vec<&Path> = relative
+            .ancestors()
+            .skip(1)
+            .filter(|x| !x.as_os_str().is_empty())
+            .collect();
+        directories.reverse();
+        directories.into_iter().any(|x| self.matches(x, true)) || self.matches(&relative, is_dir)
+    }
+
+    /// the last pattern matching the path decides whether it is ignored
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let mut ignored = false;
+        for pattern in &self.patterns {
+            if (is_dir || !pattern.dir_only) && pattern.pattern.matches_path_with(path, options) {
+                ignored = !pattern.negated;
+            }
+        }
+        ignored
+    }
+
+    /// the path relative to the directory of the ignore file
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        let path = normalize(path);
+        let root = if path.is_absolute() {
+            normalize(&std::env::current_dir().ok()?.join(&self.root))
+        } else {
+            normalize(&self.root)
+        };
+        let relative = path.strip_prefix(root).ok()?;
+        if relative
+            .components()
+            .any(|x| !matches!(x, Component::Normal(_)))
+        {
+            return None;
+        }
+        Some(relative.to_path_buf())
+    }
+}
+
+#[test]
+fn ignored_paths() {
+    let contents = "# helpers\ntests/\n*.test.sh\n!keep.test.sh\n/lib/old.sh\n";
+    let ignore = IgnoreFile::parse(Path::new("."), contents).unwrap();
+
+    assert!(ignore.is_ignored(Path::new("./lib/tests"), true));
+    assert!(ignore.is_ignored(Path::new("./lib/tests/helper.sh"), false));
+    assert!(ignore.is_ignored(Path::new("tests/a.sh"), false));
+    assert!(!ignore.is_ignored(Path::new("lib/tests"), false));
+    assert!(ignore.is_ignored(Path::new("./lib/a.test.sh"), false));
+    assert!(!ignore.is_ignored(Path::new("./lib/nested/keep.test.sh"), false));
+    assert!(ignore.is_ignored(Path::new("lib/old.sh"), false));
+    assert!(!ignore.is_ignored(Path::new("src/lib/old.sh"), false));
+    assert!(!ignore.is_ignored(Path::new("./lib/a.sh"), false));
+    assert!(!ignore.is_ignored(Path::new("../tests/a.sh"), false));
+
+    let ignore = IgnoreFile::parse(Path::new("./project"), "*.test.sh\n").unwrap();
+    assert!(ignore.is_ignored(Path::new("project/a.test.sh"), false));
+    assert!(!ignore.is_ignored(Path::new("other/a.test.sh"), false));
+
+    assert!(IgnoreFile::parse(Path::new("."), "[a.sh\n").is_err());
+}
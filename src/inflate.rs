@@ -0,0 +1,352 @@
+//! decompression of raw deflate data, for `.tar.gz` and `.zip` archives
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// the order of the code length code lengths of a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const MAX_BITS: usize = 15;
+
+/// the bits of the compressed data, least significant bit of every byte first
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn take(&mut self, count: u32) -> Result<u32, String> {
+        while self.count < count {
+            let byte = self
+                .data
+                .get(self.position)
+                .ok_or("unexpected end of the compressed data")?;
+            self.position += 1;
+            self.buffer |= u32::from(*byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// skip to the start of the next byte, for a stored block
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or("unexpected end of the compressed data")?;
+        self.position += len;
+        Ok(bytes)
+    }
+}
+
+/// a canonical Huffman code, the symbols sorted by the length of their code
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0; MAX_BITS + 1];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+
+        let mut left: i32 = 1;
+        for count in &counts[1..] {
+            left = (left << 1) - i32::from(*count);
+            if left < 0 {
+                return Err(String::from("invalid Huffman code lengths"));
+            }
+        }
+
+        let mut offsets = [0; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len] as usize;
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1]];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize]] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for count in &self.counts[1..] {
+            let count = i32::from(*count);
+            code |= bits.take(1)? as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("invalid Huffman code"))
+    }
+}
+
+/// the decompressed data and the number of compressed bytes it used
+///
+/// fails as soon as the decompressed data would be larger than `limit` bytes
+pub fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut bits = Bits {
+        data,
+        position: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut output = Vec::new();
+
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored(&mut bits, &mut output, limit)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                codes(&mut bits, &mut output, limit, &literals, &distances)?
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut output, limit, &literals, &distances)?
+            }
+            _ => return Err(String::from("invalid block type")),
+        }
+        if last {
+            return Ok((output, bits.position));
+        }
+    }
+}
+
+/// a block without compression
+fn stored(bits: &mut Bits, output: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    bits.align();
+    let header = bits.bytes(4)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(String::from("invalid stored block length"));
+    }
+    check_limit(output.len() + len as usize, limit)?;
+    output.extend_from_slice(bits.bytes(len as usize)?);
+    Ok(())
+}
+
+/// the literal and distance codes of a block compressed with the fixed codes
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// the literal and distance codes described at the start of a dynamic block
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[*index] = bits.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (len, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or("repeated code length without a previous length")?;
+                (previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(len, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(String::from("too many code lengths"));
+    }
+    if lengths[256] == 0 {
+        return Err(String::from("missing end of block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+/// the literals and back references of a compressed block, until the end of the block
+fn codes(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        if symbol < 256 {
+            check_limit(output.len() + 1, limit)?;
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let index = symbol - 257;
+        if index >= LENGTH_BASE.len() {
+            return Err(String::from("invalid length code"));
+        }
+        let len = LENGTH_BASE[index] as usize + bits.take(LENGTH_EXTRA[index])? as usize;
+
+        let index = distances.decode(bits)? as usize;
+        if index >= DISTANCE_BASE.len() {
+            return Err(String::from("invalid distance code"));
+        }
+        let distance = DISTANCE_BASE[index] as usize + bits.take(DISTANCE_EXTRA[index])? as usize;
+        if distance > output.len() {
+            return Err(String::from("distance before the start of the data"));
+        }
+
+        check_limit(output.len() + len, limit)?;
+        // the copied bytes can overlap with the bytes being written
+        let start = output.len() - distance;
+        for offset in 0..len {
+            output.push(output[start + offset]);
+        }
+    }
+}
+
+fn check_limit(size: usize, limit: usize) -> Result<(), String> {
+    if size > limit {
+        return Err(format!(
+            "the decompressed data is larger than {} bytes",
+            limit
+        ));
+    }
+    Ok(())
+}
+
+/// the CRC-32 checksum used by gzip and zip
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[test]
+fn inflates_blocks() {
+    // `hallo hallo hallo` as a stored block and with the fixed codes
+    let stored = [
+        0x01, 0x11, 0x00, 0xee, 0xff, b'h', b'a', b'l', b'l', b'o', b' ', b'h', b'a', b'l', b'l',
+        b'o', b' ', b'h', b'a', b'l', b'l', b'o',
+    ];
+    let fixed = [0xcb, 0x48, 0xcc, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00];
+
+    for data in [&stored[..], &fixed[..]] {
+        let (output, used) = inflate(data, 17).unwrap();
+        assert_eq!(b"hallo hallo hallo".to_vec(), output);
+        assert_eq!(data.len(), used);
+
+        // the back reference of the fixed block is over the limit as well
+        assert_eq!(
+            Err(String::from(
+                "the decompressed data is larger than 10 bytes"
+            )),
+            inflate(data, 10)
+        );
+    }
+    assert!(inflate(&stored[..10], 17).is_err());
+}
+
+#[test]
+fn inflates_dynamic_block() {
+    // 40 `a`, 20 `b`, 10 `c` and 5 `d` with Huffman codes made for this data
+    let dynamic = [
+        0x05, 0xc1, 0x01, 0x01, 0x00, 0x00, 0x08, 0xc3, 0xa0, 0xac, 0xec, 0xf6, 0xcf, 0x20, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x54, 0x55, 0x55, 0x55, 0x55, 0x6d, 0xdb, 0xb6, 0x6d, 0x77, 0x77,
+        0xf7,
+    ];
+
+    let (output, used) = inflate(&dynamic, 1024).unwrap();
+    let expected = [
+        "a".repeat(40),
+        "b".repeat(20),
+        "c".repeat(10),
+        "d".repeat(5),
+    ]
+    .concat();
+    assert_eq!(expected.into_bytes(), output);
+    assert_eq!(dynamic.len(), used);
+    assert!(inflate(&dynamic, 74).is_err());
+    assert!(inflate(&dynamic[..20], 1024).is_err());
+}
+
+#[test]
+fn rejects_malformed_data() {
+    // block type 3 does not exist
+    assert_eq!(
+        Err(String::from("invalid block type")),
+        inflate(&[0x07], 1024)
+    );
+    // the length of a stored block is followed by its complement
+    assert_eq!(
+        Err(String::from("invalid stored block length")),
+        inflate(&[0x01, 0x11, 0x00, 0x00, 0x00], 1024)
+    );
+    // a back reference before the start of the data
+    assert_eq!(
+        Err(String::from("distance before the start of the data")),
+        inflate(&[0x03, 0x02, 0x00], 1024)
+    );
+    assert!(inflate(&[], 1024).is_err());
+}
+
+#[test]
+fn crc32_check_value() {
+    assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+}
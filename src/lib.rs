@@ -0,0 +1,6231 @@
+use glob::Pattern;
+use serde::{Deserialize as _, Deserializer};
+use serde_derive::Deserialize;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+mod archive;
+mod builder;
+mod compat;
+mod diff;
+mod heredoc;
+mod ignore;
+mod inflate;
+mod loader;
+pub mod report;
+mod shellcheck;
+mod source_map;
+mod toplevel;
+
+pub use archive::ArchiveLoader;
+pub use builder::ArgsBuilder;
+use heredoc::{Heredoc, Literal};
+use ignore::IgnoreFile;
+pub use loader::{DiskLoader, SourceLoader};
+pub use source_map::SourceSpan;
+
+const CIRCULAR_CUT_OFF: usize = 512;
+const DEFAULT_CONFIGS: &[&str] = &["bash_bundler.toml", ".bashbundler.toml"];
+const ALLOWED_EXTENSIONS: &[Option<&str>] = &[
+    Some("sh"),
+    Some("bash"),
+    Some("ksh"),
+    Some("zsh"),
+    Some("csh"),
+];
+const MAIN_GUARD_START: &str = r#"if [[ "${BASH_SOURCE[0]}" == "${0}" ]]; then"#;
+const MAIN_GUARD_END: &str = "fi";
+/// appended to the import lines kept with `--keep-source-ref`, these lines are not imports anymore
+const SOURCE_REF_SUFFIX: &str = " (inlined)";
+/// after the comment prefix, the line of the root file where the libraries go with `--root-order marker`
+const LIBRARIES_MARKER: &str = " (libraries)";
+const SLOWEST_READS: usize = 5;
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    bundler: toml::value::Table,
+}
+
+impl Config {
+    /// read the config, on top of the config it `extends`
+    fn load(path: &Path) -> Result<Config, Error> {
+        Self::load_extending(path, &mut Vec::new())
+    }
+
+    /// `chain` contains the configs extending this one, to detect cycles
+    fn load_extending(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Config, Error> {
+        let with_path = |source| Error::IoWithPath {
+            path: path.to_path_buf(),
+            source,
+        };
+        let identity = std::fs::canonicalize(path).map_err(with_path)?;
+        if chain.contains(&identity) {
+            return Err(Error::ConfigCycle(path.to_path_buf()));
+        }
+        chain.push(identity);
+
+        let contents = std::fs::read(path).map_err(with_path)?;
+        let mut config: Config = toml::from_slice(&contents)?;
+        resolve_aliases(&mut config.bundler, &parent_dir(path));
+        let base = match config.bundler.remove("extends") {
+            None => return Ok(config),
+            Some(toml::Value::String(base)) => parent_dir(path).join(base),
+            Some(_) => {
+                return Err(Error::Toml(serde::de::Error::custom(
+                    "`extends` should be a path to a config",
+                )))
+            }
+        };
+
+        let mut base = Self::load_extending(&base, chain)?;
+        merge_tables(&mut base.bundler, config.bundler);
+        Ok(base)
+    }
+
+    /// the arguments of the `[bundler]` table, with the values of the `[bundler.<profile>]` table on top
+    fn into_args(mut self, profile: Option<&str>) -> Result<Args, Error> {
+        if let Some(name) = profile {
+            match self.bundler.remove(name) {
+                Some(toml::Value::Table(table)) => self.bundler.extend(table),
+                _ => return Err(Error::Profile(String::from(name))),
+            }
+        }
+
+        Ok(toml::Value::Table(self.bundler).try_into()?)
+    }
+}
+
+/// make the paths of the `aliases` tables of the `[bundler]` table and its profiles relative to the directory of the config
+fn resolve_aliases(bundler: &mut toml::value::Table, directory: &Path) {
+    for (name, value) in bundler.iter_mut() {
+        match value {
+            toml::Value::Table(aliases) if name == "aliases" => {
+                for (_, value) in aliases.iter_mut() {
+                    if let toml::Value::String(path) = value {
+                        *path = directory.join(&path).to_string_lossy().into_owned();
+                    }
+                }
+            }
+            toml::Value::Table(profile) => resolve_aliases(profile, directory),
+            _ => (),
+        }
+    }
+}
+
+/// Collects/bundles bash files into one file.
+///
+/// By default uses the saver `# import ./filename.sh` syntax to include other bash files.
+/// But can be set to use the already existing `source ./filename.sh` syntax.
+///
+/// There is a difference between the `import` and `source` import statements.
+/// The `import` is relative to the current file, but the `source` is relative from the base/root file.
+///
+/// for instance:
+/// your root file is in `src/my_project.sh` that looks like:
+///
+/// ```sh
+/// # import ./utils/utils.sh
+///
+/// my_func "hallo"
+/// ```
+///
+/// and utils.sh looks like:
+///
+/// ```sh
+/// # import ./other.sh # other contains the my_func
+/// ```
+/// this will import from file `./src/utils/other.sh`
+///
+/// With the source it is relative from the root file so like:
+///
+/// ```sh
+/// source ./utils/utils.sh
+///
+/// my_func "hallo"
+/// ```
+///
+/// and `utils.sh` looks like:
+///
+/// ```sh
+/// source ./utils/other.sh # other contains the my_func
+/// ```
+///
+/// This is done so that files containing the `source` can just be used in normal bash.
+/// ```sh
+/// cd src
+/// ./my_project.sh
+/// ```
+///
+/// Configs can be used to override/save arguments. Config should look like:
+///
+/// ```toml
+///
+/// [bundler]
+/// replace_source = true
+/// replace_comment = false
+/// root_path = "./tests/source.sh"
+/// output = "./bundled.sh"
+/// ```
+///
+/// Arguments given on the command line override the values in the config.
+/// Without `--config` and a root file, `bash_bundler.toml` or `.bashbundler.toml` in the current directory is used.
+/// Profiles in `[bundler.<name>]` tables, selected with `--profile <name>`, override the `[bundler]` values.
+/// A config can build on another config with `extends = "../base.toml"`, relative to the config.
+///
+/// Exit codes:
+///     1  other errors
+///     2  unresolved, empty or outside the root imports with `--strict`, or other warnings with `--fail-on-warning`
+///     3  circular imports
+///     4  reading or writing files
+///     5  invalid config
+/// With `run` the exit code of the bundled script is returned.
+///
+#[derive(Debug, StructOpt, Deserialize)]
+#[structopt(verbatim_doc_comment, setting = AppSettings::SubcommandsNegateReqs)]
+#[serde(default)]
+pub struct Args {
+    /// starting or `main` bash file
+    #[structopt(parse(try_from_str = existing_path))]
+    root_path: Option<PathBuf>,
+    /// bundle only this file and its imports, using it as the root file instead of the configured one
+    #[structopt(long, value_name = "path", parse(try_from_str = existing_path))]
+    only: Option<PathBuf>,
+    #[serde(skip)]
+    /// path to your toml config, defaults to `bash_bundler.toml` in the current directory
+    #[structopt(short, long, parse(try_from_str = existing_path))]
+    config: Option<PathBuf>,
+    #[serde(skip)]
+    /// use the values of the `[bundler.<profile>]` table of the config
+    #[structopt(long, value_name = "name")]
+    profile: Option<String>,
+    /// write the bundle to this file instead of stdout
+    #[structopt(short, long, value_name = "path")]
+    output: Option<PathBuf>,
+    /// a relative `--output` path is relative to the current directory (`cwd`) or the directory of the root file (`root`)
+    #[structopt(long, default_value = "cwd", possible_values = &["cwd", "root"])]
+    output_relative_to: OutputBase,
+    /// lock `.<output>.lock` next to the output while writing, so parallel runs do not interleave
+    #[structopt(long)]
+    concurrency_safe_output: bool,
+    /// do not rewrite the output file when its contents would stay the same, keeping its modification time
+    #[structopt(long)]
+    if_changed: bool,
+    #[serde(skip)]
+    /// print the paths and sizes of the files that would be written, without writing them or printing the bundle
+    #[structopt(long)]
+    dry_run: bool,
+    /// write the SHA-256 hash, size and path of every included file to this file
+    #[structopt(long, value_name = "path")]
+    manifest: Option<PathBuf>,
+    /// pipe the contents of every file through this shell command before looking for imports, `$BASH_BUNDLER_FILE` is the file
+    #[structopt(long, value_name = "command")]
+    preprocess: Option<String>,
+    /// resolve imports to the files in this `.tar`, `.tar.gz` or `.zip` archive first, entries are relative to the root file
+    #[structopt(long, value_name = "path")]
+    archive: Option<PathBuf>,
+    /// enable the 'source ./file.sh` (or '. ./file.sh`) syntax
+    #[structopt(long = "enable-source")]
+    replace_source: bool,
+    /// disable the '# import ./file.sh` syntax
+    #[structopt(long = "disable-comment", parse(from_flag = std::ops::Not::not))]
+    replace_comment: bool,
+    /// enable both syntaxes, failing if a `source` path matches different files relative to the root file and the current file
+    #[structopt(long, conflicts_with = "replace-comment")]
+    auto: bool,
+    /// print how many imports of each syntax the root file and its imports use, without bundling
+    #[serde(skip)]
+    #[structopt(long)]
+    list_styles: bool,
+    /// the comment token used in the '# import ./file.sh` syntax
+    #[structopt(long, default_value = "#", value_name = "prefix")]
+    comment_prefix: String,
+    /// continue a '# import ./file.sh` path ending in `\` on the next comment line
+    #[structopt(long)]
+    import_continuation: bool,
+    /// inline the '# import? ./file.sh` imports, which are left as they are by default
+    #[structopt(long)]
+    optional_imports: bool,
+    /// resolve the '# import ./file.sh` syntax relative to this directory, instead of the current file
+    #[structopt(long, value_name = "dir")]
+    import_base: Option<PathBuf>,
+    /// retry imports that are not found relative to this directory, in the order they are given
+    #[structopt(long, value_name = "dir", number_of_values = 1)]
+    search_path: Vec<PathBuf>,
+    /// the `[bundler.aliases]` table of the config, `"@std" = "./vendor/std"` resolves `# import @std/log.sh`
+    /// to `./vendor/std/log.sh`, relative to the directory of the config
+    #[structopt(skip)]
+    aliases: BTreeMap<String, PathBuf>,
+    /// retry imports that are not found with a file name that only differs in case
+    #[structopt(long)]
+    ignore_case: bool,
+    /// resolve the `source ./file.sh` syntax relative to the current file, instead of the root file
+    #[structopt(long)]
+    source_relative_to_file: bool,
+    /// line ending used in the output, `preserve` uses the one of the root file
+    #[structopt(long, default_value = "lf", possible_values = &["lf", "crlf", "preserve"])]
+    line_ending: LineEnding,
+    /// append a call to this function, passing all arguments, at the end of the output
+    #[structopt(long, value_name = "name")]
+    call_main: Option<String>,
+    /// remove the shebang line from the output, for bundles that are meant to be sourced
+    #[structopt(long)]
+    no_shebang: bool,
+    /// use this shebang line, replacing the shebang of the root file or inserting it
+    #[structopt(long, value_name = "line", conflicts_with = "no-shebang", parse(try_from_str = shebang_line))]
+    #[serde(deserialize_with = "deserialize_shebang")]
+    shebang: Option<String>,
+    /// leave imports matching this glob untouched, matched against the resolved and the typed path
+    #[structopt(long, value_name = "glob", number_of_values = 1)]
+    #[serde(deserialize_with = "deserialize_patterns")]
+    exclude: Vec<Pattern>,
+    /// text added as comment lines at the top of the output, after the shebang
+    #[structopt(long, value_name = "text")]
+    banner: Option<String>,
+    /// file containing the banner text
+    #[structopt(long, value_name = "path", conflicts_with = "banner", parse(try_from_str = existing_path))]
+    banner_file: Option<PathBuf>,
+    /// insert the contents of this file at the start of the output, after the shebang line
+    #[structopt(long, value_name = "path", parse(try_from_str = existing_path))]
+    prepend: Option<PathBuf>,
+    /// insert the contents of this file at the end of the output
+    #[structopt(long, value_name = "path", parse(try_from_str = existing_path))]
+    append: Option<PathBuf>,
+    /// resolve the imports in the `--prepend` and `--append` files, instead of inserting them verbatim
+    #[structopt(long)]
+    resolve_injected: bool,
+    /// collapse consecutive blank lines in the output into one, outside of heredocs
+    #[structopt(long)]
+    squeeze_blank: bool,
+    /// normalize the leading whitespace of the output to spaces or tabs, outside of heredocs
+    #[structopt(long, value_name = "style", possible_values = &["spaces", "tabs"])]
+    reindent: Option<Indent>,
+    /// number of spaces per indentation level used by `--reindent`
+    #[structopt(long, default_value = "4", value_name = "N")]
+    indent_width: usize,
+    /// log the resolution steps to stderr, repeat for more detail
+    #[structopt(short, long, parse(from_occurrences))]
+    verbose: u8,
+    /// replace invalid UTF-8 in the files with the replacement character, instead of failing
+    #[structopt(long)]
+    lossy: bool,
+    /// fail if the root file or an imported file is not readable
+    #[structopt(long)]
+    require_readable: bool,
+    /// fail if the root file is not executable
+    #[structopt(long)]
+    require_executable: bool,
+    /// warn when an import resolves to a file outside this directory
+    #[structopt(long, value_name = "dir")]
+    root_dir: Option<PathBuf>,
+    /// fail instead of warning for unresolved and empty imports, imports outside `--root-dir` and functions found by `--normalize-functions`
+    #[structopt(long)]
+    strict: bool,
+    /// report every unresolved, empty or unreadable import at the end, instead of stopping at the first
+    #[structopt(long)]
+    keep_going: bool,
+    /// do not expand `$VAR` and `${VAR}` in import paths
+    #[structopt(long)]
+    no_env: bool,
+    /// replace `\` with `/` in import paths, so paths written on Windows resolve everywhere
+    #[structopt(long)]
+    normalize_separators: bool,
+    /// print statistics about the bundle to stderr
+    #[structopt(long)]
+    stats: bool,
+    /// print how long loading and resolving took, and the slowest files to read, to stderr
+    #[structopt(long)]
+    timings: bool,
+    /// print the tree of loaded files and their imports to stderr
+    #[structopt(long)]
+    debug_tree: bool,
+    /// print every import to stderr with the files it resolved to, or why it did not resolve
+    #[structopt(long)]
+    explain: bool,
+    /// show the paths in the manifest, tree, explanations, unused files and markers as typed, relative or absolute
+    #[structopt(long, default_value = "as-typed", possible_values = &["as-typed", "relative", "absolute"], value_name = "style")]
+    path_style: PathStyle,
+    /// do not end the output with a newline and do not print stats or warnings
+    #[structopt(short, long, alias = "no-trailing-newline")]
+    quiet: bool,
+    /// exit with an error when there are warnings, like unresolved imports
+    #[structopt(long)]
+    fail_on_warning: bool,
+    #[serde(skip)]
+    /// print errors for people (`human`) or as a JSON object on one line (`json`)
+    #[structopt(long, default_value = "human", possible_values = &["human", "json"], value_name = "format")]
+    error_format: ErrorFormat,
+    /// also inline the files in subdirectories when importing a directory
+    #[structopt(long)]
+    recursive_dir: bool,
+    /// only inline imports up to this many levels deep, deeper import lines are left intact
+    #[structopt(long, value_name = "N")]
+    depth: Option<usize>,
+    /// fail when the output gets larger than this many bytes
+    #[structopt(long, value_name = "N")]
+    max_output_bytes: Option<usize>,
+    /// fail when the root file or an imported file is larger than this many bytes
+    #[structopt(long, value_name = "N")]
+    max_file_size: Option<usize>,
+    /// only inline the imports of the root file, same as `--depth 1`
+    #[structopt(long, conflicts_with = "depth")]
+    flatten_once: bool,
+    /// put exactly one blank line between inlined files, trimming their leading and trailing blank lines
+    #[structopt(long)]
+    blank_between_files: bool,
+    /// remove the leading and trailing blank lines of every imported file before inlining it
+    #[structopt(long)]
+    trim_imported: bool,
+    /// `inline` replaces every import with the file, `flatten` puts every file once before the files importing it
+    #[structopt(long, default_value = "inline", possible_values = &["inline", "flatten"])]
+    mode: Mode,
+    /// where the root file goes in flatten mode, `marker` splits it at a `# (libraries)` line
+    #[structopt(long, default_value = "last", possible_values = &["last", "first", "marker"])]
+    root_order: RootOrder,
+    /// put this line between the files inlined into the root file, like `# ----`
+    #[structopt(
+        long,
+        default_value = "",
+        hide_default_value = true,
+        value_name = "line"
+    )]
+    separator: String,
+    /// leave a `# (inlined ./file.sh)` comment before the contents of every resolved import
+    #[structopt(long)]
+    keep_import_markers: bool,
+    /// keep the import line as a `# import ./file.sh (inlined)` comment before the contents it imports
+    #[structopt(long)]
+    keep_source_ref: bool,
+    /// keep the import line as is, with `# (begin ./file.sh)` and `# (end ./file.sh)` around its contents
+    #[structopt(long)]
+    keep_directives: bool,
+    /// only run the top level statements of the root file when the bundle is executed, not when sourced
+    #[structopt(long)]
+    main_guard: bool,
+    /// comment out the top level commands of the inlined files, like demo calls, keeping the definitions
+    #[structopt(long)]
+    strip_toplevel_from_imports: bool,
+    /// warn when a function is defined more than once in the output
+    #[structopt(long)]
+    normalize_functions: bool,
+    /// warn about bash only syntax in the inlined files when the root file has a `#!/bin/sh` shebang
+    #[structopt(long)]
+    compat_lint: bool,
+    /// warn when the interpreter of the shebang line of the bundle is not installed, fail with `--strict`
+    #[structopt(long)]
+    interpreter_check: bool,
+    /// lint the bundle with `shellcheck`, failing on errors
+    #[structopt(long)]
+    shellcheck: bool,
+    #[serde(skip)]
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// use the value of `cli` for every field it sets to a non default value
+macro_rules! merge_fields {
+    ($config:ident, $cli:ident, $($field:ident),* $(,)?) => {{
+        let default = Args::default();
+        $(
+            if $cli.$field != default.$field {
+                $config.$field = $cli.$field;
+            }
+        )*
+    }};
+}
+
+impl Args {
+    /// merge the arguments given on the command line over the arguments from the config
+    fn merge(mut self, cli: Args) -> Args {
+        merge_fields!(
+            self,
+            cli,
+            root_path,
+            only,
+            output,
+            output_relative_to,
+            concurrency_safe_output,
+            if_changed,
+            manifest,
+            archive,
+            preprocess,
+            replace_comment,
+            replace_source,
+            auto,
+            comment_prefix,
+            import_continuation,
+            optional_imports,
+            import_base,
+            search_path,
+            aliases,
+            ignore_case,
+            source_relative_to_file,
+            line_ending,
+            call_main,
+            no_shebang,
+            shebang,
+            exclude,
+            banner,
+            banner_file,
+            prepend,
+            append,
+            resolve_injected,
+            squeeze_blank,
+            reindent,
+            indent_width,
+            verbose,
+            lossy,
+            require_readable,
+            require_executable,
+            root_dir,
+            strict,
+            keep_going,
+            no_env,
+            normalize_separators,
+            stats,
+            timings,
+            debug_tree,
+            explain,
+            path_style,
+            quiet,
+            fail_on_warning,
+            recursive_dir,
+            depth,
+            max_output_bytes,
+            max_file_size,
+            flatten_once,
+            blank_between_files,
+            trim_imported,
+            mode,
+            root_order,
+            separator,
+            keep_import_markers,
+            keep_source_ref,
+            keep_directives,
+            main_guard,
+            strip_toplevel_from_imports,
+            normalize_functions,
+            compat_lint,
+            interpreter_check,
+            shellcheck,
+        );
+        self.config = cli.config;
+        self.profile = cli.profile;
+        self.dry_run = cli.dry_run;
+        self.list_styles = cli.list_styles;
+        self.command = cli.command;
+        self
+    }
+
+    /// how errors are printed by the binary
+    pub fn error_format(&self) -> ErrorFormat {
+        self.error_format
+    }
+
+    /// the path to write the bundle to, a relative path is resolved according to `output_relative_to`
+    fn output_path(&self) -> Option<PathBuf> {
+        let output = self.output.as_ref()?;
+        match (self.output_relative_to, &self.root_path) {
+            (OutputBase::Root, Some(root)) if output.is_relative() => {
+                Some(parent_dir(root).join(output))
+            }
+            _ => Some(output.clone()),
+        }
+    }
+
+    /// write the message to stderr if the verbosity is at least `level`
+    fn log(&self, level: u8, message: std::fmt::Arguments) {
+        if self.verbose >= level {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// the import path with `\` replaced by `/` with `--normalize-separators`
+    ///
+    /// only the path is changed, a file name containing a `\` can then not be imported
+    fn normalize_separators<'a>(&self, path: Cow<'a, str>) -> Cow<'a, str> {
+        if self.normalize_separators && path.contains('\\') {
+            Cow::Owned(path.replace('\\', "/"))
+        } else {
+            path
+        }
+    }
+
+    /// expand the environment variables in the import path
+    fn expand_env<'a>(&self, input: &'a str) -> Result<Cow<'a, str>, Error> {
+        if self.no_env || !input.contains('$') {
+            return Ok(Cow::Borrowed(input));
+        }
+
+        let mut output = String::new();
+        let mut rest = input;
+        while let Some(index) = rest.find('$') {
+            output.push_str(&rest[..index]);
+            rest = &rest[index + 1..];
+
+            let (name, remaining) = match rest.strip_prefix('{') {
+                Some(braced) => match braced.find('}') {
+                    Some(end) => (&braced[..end], &braced[end + 1..]),
+                    None => ("", rest),
+                },
+                None => {
+                    let end = rest
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len());
+                    (&rest[..end], &rest[end..])
+                }
+            };
+
+            if name.is_empty() {
+                output.push('$');
+                continue;
+            }
+            let value =
+                std::env::var(name).map_err(|_| Error::UnsetVariable(String::from(name)))?;
+            output.push_str(&value);
+            rest = remaining;
+        }
+        output.push_str(rest);
+
+        Ok(Cow::Owned(output))
+    }
+
+    /// the import path with the alias in its first component replaced by the path of the alias
+    ///
+    /// a first component starting with `@` is always an alias, failing when it is not in the table
+    fn expand_alias(&self, input: &str) -> Result<Option<PathBuf>, Error> {
+        let (name, rest) = match input.split_once('/') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (input, None),
+        };
+        if !name.starts_with('@') {
+            return Ok(None);
+        }
+        let path = self
+            .aliases
+            .get(name)
+            .ok_or_else(|| Error::UnknownAlias(String::from(name)))?;
+        Ok(Some(match rest {
+            Some(rest) => path.join(rest),
+            None => path.clone(),
+        }))
+    }
+
+    /// fail if `--require-readable` is set and the file can not be read
+    fn check_readable(&self, path: &Path, loader: &dyn SourceLoader) -> Result<(), Error> {
+        if self.require_readable && !loader.is_readable(path) {
+            return Err(Error::Permission {
+                path: path.to_path_buf(),
+                permission: "readable",
+            });
+        }
+        Ok(())
+    }
+
+    /// fail if the output size in bytes is over the `--max-output-bytes` limit
+    fn check_output_size(&self, size: usize) -> Result<(), Error> {
+        match self.max_output_bytes {
+            Some(limit) if size > limit => Err(Error::OutputTooLarge(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// fail if a source file of this size in bytes is over the `--max-file-size` limit
+    fn check_file_size(&self, path: &Path, size: u64) -> Result<(), Error> {
+        match self.max_file_size {
+            Some(limit) if size > limit as u64 => Err(Error::FileTooLarge {
+                path: path.to_path_buf(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// the maximum number of levels to inline
+    fn max_depth(&self) -> Option<usize> {
+        if self.flatten_once {
+            Some(1)
+        } else {
+            self.depth
+        }
+    }
+
+    /// whether the import should be left untouched
+    fn is_excluded(&self, text: &str, path: &Path) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches(text) || pattern.matches_path(path))
+    }
+}
+
+fn deserialize_shebang<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|x| shebang_line(&x).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// set the values of `overrides` in `base`, tables that are in both are merged
+fn merge_tables(base: &mut toml::value::Table, overrides: toml::value::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => {
+                merge_tables(base, value)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// a shebang line has to start with `#!`
+fn shebang_line(input: &str) -> Result<String, String> {
+    if input.starts_with("#!") && !input.contains('\n') {
+        Ok(String::from(input))
+    } else {
+        Err(format!(
+            "shebang `{}` should be one line starting with `#!`",
+            input
+        ))
+    }
+}
+
+fn deserialize_patterns<'de, D>(deserializer: D) -> Result<Vec<Pattern>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|x| Pattern::new(x).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Compare two bundled files and print the changed lines as a unified diff
+    Diff {
+        /// the old bundle
+        #[structopt(parse(try_from_str = existing_path))]
+        old: PathBuf,
+        /// the new bundle
+        #[structopt(parse(try_from_str = existing_path))]
+        new: PathBuf,
+    },
+    /// Print the shell files in a directory that are not imported by any of the roots
+    CheckUnused {
+        /// the directory to search for shell files, including subdirectories
+        #[structopt(parse(try_from_str = existing_path))]
+        directory: PathBuf,
+        /// the root files whose imports are followed
+        #[structopt(required = true, parse(try_from_str = existing_path))]
+        roots: Vec<PathBuf>,
+    },
+    /// Print the import statements left in a bundled file, failing if there are any
+    Validate {
+        /// the bundled file
+        #[structopt(parse(try_from_str = existing_path))]
+        bundle: PathBuf,
+    },
+    /// Bundle the file to a temporary file and execute it, arguments after `--` are passed to the script
+    Run {
+        /// starting or `main` bash file
+        #[structopt(parse(try_from_str = existing_path))]
+        root: PathBuf,
+        /// the shell that executes the bundle
+        #[structopt(long, default_value = "bash")]
+        interpreter: String,
+        /// the arguments of the script
+        #[structopt(last = true)]
+        arguments: Vec<String>,
+    },
+}
+
+impl Default for Args {
+    fn default() -> Args {
+        Args {
+            root_path: None,
+            only: None,
+            config: None,
+            profile: None,
+            output: None,
+            output_relative_to: OutputBase::default(),
+            concurrency_safe_output: false,
+            if_changed: false,
+            dry_run: false,
+            manifest: None,
+            archive: None,
+            preprocess: None,
+            replace_comment: true,
+            replace_source: false,
+            auto: false,
+            list_styles: false,
+            comment_prefix: String::from("#"),
+            import_continuation: false,
+            optional_imports: false,
+            import_base: None,
+            search_path: Vec::new(),
+            aliases: BTreeMap::new(),
+            ignore_case: false,
+            source_relative_to_file: false,
+            line_ending: LineEnding::default(),
+            call_main: None,
+            no_shebang: false,
+            shebang: None,
+            exclude: Vec::new(),
+            banner: None,
+            banner_file: None,
+            prepend: None,
+            append: None,
+            resolve_injected: false,
+            squeeze_blank: false,
+            reindent: None,
+            indent_width: 4,
+            verbose: 0,
+            lossy: false,
+            require_readable: false,
+            require_executable: false,
+            root_dir: None,
+            strict: false,
+            keep_going: false,
+            no_env: false,
+            normalize_separators: false,
+            stats: false,
+            timings: false,
+            debug_tree: false,
+            explain: false,
+            path_style: PathStyle::default(),
+            quiet: false,
+            fail_on_warning: false,
+            recursive_dir: false,
+            depth: None,
+            max_output_bytes: None,
+            max_file_size: None,
+            flatten_once: false,
+            blank_between_files: false,
+            trim_imported: false,
+            mode: Mode::default(),
+            root_order: RootOrder::default(),
+            separator: String::new(),
+            keep_import_markers: false,
+            keep_source_ref: false,
+            keep_directives: false,
+            main_guard: false,
+            strip_toplevel_from_imports: false,
+            normalize_functions: false,
+            compat_lint: false,
+            interpreter_check: false,
+            error_format: ErrorFormat::default(),
+            shellcheck: false,
+            command: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    Preserve,
+}
+
+impl std::str::FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<LineEnding, String> {
+        match input {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            "preserve" => Ok(LineEnding::Preserve),
+            _ => Err(format!("invalid line ending: {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputBase {
+    #[default]
+    Cwd,
+    Root,
+}
+
+impl std::str::FromStr for OutputBase {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<OutputBase, String> {
+        match input {
+            "cwd" => Ok(OutputBase::Cwd),
+            "root" => Ok(OutputBase::Root),
+            _ => Err(format!("invalid output base: {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Inline,
+    Flatten,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Mode, String> {
+        match input {
+            "inline" => Ok(Mode::Inline),
+            "flatten" => Ok(Mode::Flatten),
+            _ => Err(format!("invalid mode: {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// where the lines of the root file go relative to the libraries in flatten mode
+pub enum RootOrder {
+    #[default]
+    Last,
+    First,
+    /// the lines before the marker line go first, the rest after the libraries
+    Marker,
+}
+
+impl std::str::FromStr for RootOrder {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<RootOrder, String> {
+        match input {
+            "last" => Ok(RootOrder::Last),
+            "first" => Ok(RootOrder::First),
+            "marker" => Ok(RootOrder::Marker),
+            _ => Err(format!("invalid root order: {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<ErrorFormat, String> {
+        match input {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("invalid error format: {}", input)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathStyle {
+    #[default]
+    AsTyped,
+    Relative,
+    Absolute,
+}
+
+impl std::str::FromStr for PathStyle {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<PathStyle, String> {
+        match input {
+            "as-typed" => Ok(PathStyle::AsTyped),
+            "relative" => Ok(PathStyle::Relative),
+            "absolute" => Ok(PathStyle::Absolute),
+            _ => Err(format!("invalid path style: {}", input)),
+        }
+    }
+}
+
+impl PathStyle {
+    /// the path for displaying, `relative` is relative to the current directory,
+    /// the `.` and `..` components are removed without following symlinks
+    pub fn render<'a>(self, path: &'a Path) -> Cow<'a, Path> {
+        let current_dir = match std::env::current_dir() {
+            Ok(current_dir) if self != PathStyle::AsTyped => current_dir,
+            _ => return Cow::Borrowed(path),
+        };
+        let absolute = lexical_absolute(&current_dir.join(path));
+        match self {
+            PathStyle::Relative => Cow::Owned(relative_to(&absolute, &current_dir)),
+            _ => Cow::Owned(absolute),
+        }
+    }
+}
+
+/// the absolute path with the `.` and `..` components removed
+fn lexical_absolute(path: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+/// the path relative to the directory, both absolute, going up with `..` when it is not inside the directory
+fn relative_to(path: &Path, directory: &Path) -> PathBuf {
+    let directory = lexical_absolute(directory);
+    let common = path
+        .components()
+        .zip(directory.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative: PathBuf = directory
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(path.components().skip(common));
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Indent {
+    Spaces,
+    Tabs,
+}
+
+impl std::str::FromStr for Indent {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Indent, String> {
+        match input {
+            "spaces" => Ok(Indent::Spaces),
+            "tabs" => Ok(Indent::Tabs),
+            _ => Err(format!("invalid indent style: {}", input)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    IoWithPath {
+        path: PathBuf,
+        source: io::Error,
+    },
+    NotFound(PathBuf),
+    Encoding {
+        path: PathBuf,
+        offset: usize,
+    },
+    AmbiguousSource {
+        text: String,
+        matches: Vec<PathBuf>,
+    },
+    AmbiguousCase {
+        path: PathBuf,
+        matches: Vec<PathBuf>,
+    },
+    OutputTooLarge(usize),
+    FileTooLarge {
+        path: PathBuf,
+        limit: usize,
+    },
+    Preprocess {
+        path: PathBuf,
+        message: String,
+    },
+    Archive {
+        path: PathBuf,
+        message: String,
+    },
+    Permission {
+        path: PathBuf,
+        permission: &'static str,
+    },
+    Toml(toml::de::Error),
+    Circular {
+        location: Option<Location>,
+        /// the identities of the files in the cycle, starting and ending with the same file
+        cycle: Vec<PathBuf>,
+    },
+    SelfImport(Location),
+    DuplicateFunction {
+        name: String,
+        first: Box<Location>,
+        location: Location,
+    },
+    EmptyImport {
+        location: Location,
+        path: PathBuf,
+    },
+    MissingInterpreter {
+        location: Location,
+        interpreter: String,
+    },
+    RegionNotFound {
+        location: Location,
+        path: PathBuf,
+        region: String,
+    },
+    Unresolved {
+        location: Location,
+        path: PathBuf,
+        reason: Unresolved,
+    },
+    OutsideRoot {
+        location: Location,
+        path: PathBuf,
+        root: PathBuf,
+    },
+    UnsetVariable(String),
+    UnknownAlias(String),
+    Profile(String),
+    MissingRoot,
+    ConfigCycle(PathBuf),
+    Glob {
+        pattern: PathBuf,
+        message: String,
+    },
+    Condition(String),
+    Shellcheck(String),
+    Warnings(usize),
+    ImportsLeft(usize),
+    Script(i32),
+    /// the errors collected with `--keep-going`
+    Multiple(Vec<Error>),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::IoWithPath { path, source } => {
+                write!(f, "failed to read {}: {}", path.display(), source)
+            }
+            Error::NotFound(path) => write!(f, "no such file: {}", path.display()),
+            Error::Encoding { path, offset } => write!(
+                f,
+                "{} is not valid UTF-8 at byte {}, use `--lossy` to replace the invalid bytes",
+                path.display(),
+                offset
+            ),
+            Error::AmbiguousSource { text, matches } => write!(
+                f,
+                "`source {}` matches {} relative to the root file and {} relative to the current file, \
+                use `--source-relative-to-file` to choose",
+                text,
+                matches[0].display(),
+                matches[1].display()
+            ),
+            Error::AmbiguousCase { path, matches } => {
+                let matches: Vec<_> = matches.iter().map(|x| x.display().to_string()).collect();
+                write!(
+                    f,
+                    "import {} matches multiple files ignoring case: {}",
+                    path.display(),
+                    matches.join(", ")
+                )
+            }
+            Error::OutputTooLarge(limit) => {
+                write!(f, "the output is larger than the limit of {} bytes", limit)
+            }
+            Error::FileTooLarge { path, limit } => write!(
+                f,
+                "{} is larger than the limit of {} bytes",
+                path.display(),
+                limit
+            ),
+            Error::Preprocess { path, message } => {
+                write!(f, "preprocessing {} failed: {}", path.display(), message)
+            }
+            Error::Archive { path, message } => {
+                write!(f, "failed to read archive {}: {}", path.display(), message)
+            }
+            Error::Permission { path, permission } => {
+                write!(f, "{} is not {}", path.display(), permission)
+            }
+            Error::Toml(err) => write!(f, "{}", err),
+            Error::Circular { .. } => write!(f, "Circular import found"),
+            Error::SelfImport(location) => write!(
+                f,
+                "file imports itself: {}:{}",
+                location.path.display(),
+                location.line_number + 1
+            ),
+            Error::DuplicateFunction { name, first, .. } => write!(
+                f,
+                "function `{}` is defined more than once, first at {}:{}",
+                name,
+                first.path.display(),
+                first.line_number + 1
+            ),
+            Error::EmptyImport { path, .. } => write!(f, "import {} is empty", path.display()),
+            Error::RegionNotFound { path, region, .. } => write!(
+                f,
+                "region `{}` not found in {}, expected a `# region {}` and a `# endregion {}` line",
+                region,
+                path.display(),
+                region,
+                region
+            ),
+            Error::MissingInterpreter { interpreter, .. } => write!(
+                f,
+                "interpreter `{}` of the shebang line is not installed or not on the PATH",
+                interpreter
+            ),
+            Error::Unresolved { path, reason, .. } => {
+                write!(f, "unresolved import {}: {}", path.display(), reason)
+            }
+            Error::OutsideRoot {
+                location: _,
+                path,
+                root,
+            } => write!(
+                f,
+                "import {} is outside the root directory {}",
+                path.display(),
+                root.display()
+            ),
+            Error::UnsetVariable(name) => {
+                write!(
+                    f,
+                    "environment variable `{}` used in import is not set",
+                    name
+                )
+            }
+            Error::UnknownAlias(name) => write!(
+                f,
+                "alias `{}` used in import is not in the `[bundler.aliases]` table of the config",
+                name
+            ),
+            Error::Profile(name) => write!(f, "profile `{}` not found in the config", name),
+            Error::MissingRoot => write!(
+                f,
+                "no root file given, pass a file or a config with a `root_path`"
+            ),
+            Error::ConfigCycle(path) => write!(f, "config {} extends itself", path.display()),
+            Error::Glob { pattern, message } => {
+                write!(f, "invalid glob {}: {}", pattern.display(), message)
+            }
+            Error::Condition(condition) => write!(
+                f,
+                "invalid import condition `{}`, expected `$VAR == value` or `$VAR != value`",
+                condition
+            ),
+            Error::Shellcheck(message) => write!(f, "{}", message),
+            Error::Warnings(count) => write!(f, "failing because of {} warning(s)", count),
+            Error::ImportsLeft(count) => {
+                write!(f, "found {} import statement(s) left in the bundle", count)
+            }
+            Error::Script(code) => write!(f, "the bundled script exited with code {}", code),
+            Error::Multiple(errors) => write!(f, "aborting because of {} errors", errors.len()),
+        }
+    }
+}
+
+impl Error {
+    /// the name of the kind of error, used in the `--error-format json` output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io(_) | Error::IoWithPath { .. } => "io",
+            Error::NotFound(_) => "not_found",
+            Error::Encoding { .. } => "encoding",
+            Error::AmbiguousSource { .. } => "ambiguous_source",
+            Error::AmbiguousCase { .. } => "ambiguous_case",
+            Error::OutputTooLarge(_) => "output_too_large",
+            Error::FileTooLarge { .. } => "file_too_large",
+            Error::Preprocess { .. } => "preprocess",
+            Error::Archive { .. } => "archive",
+            Error::Permission { .. } => "permission",
+            Error::Toml(_) => "config",
+            Error::Circular { .. } => "circular",
+            Error::SelfImport(_) => "self_import",
+            Error::DuplicateFunction { .. } => "duplicate_function",
+            Error::EmptyImport { .. } => "empty_import",
+            Error::MissingInterpreter { .. } => "missing_interpreter",
+            Error::RegionNotFound { .. } => "region_not_found",
+            Error::Unresolved { .. } => "unresolved",
+            Error::OutsideRoot { .. } => "outside_root",
+            Error::UnsetVariable(_) => "unset_variable",
+            Error::UnknownAlias(_) => "unknown_alias",
+            Error::Profile(_) => "profile",
+            Error::MissingRoot => "missing_root",
+            Error::ConfigCycle(_) => "config_cycle",
+            Error::Glob { .. } => "glob",
+            Error::Condition(_) => "condition",
+            Error::Shellcheck(_) => "shellcheck",
+            Error::Warnings(_) => "warnings",
+            Error::ImportsLeft(_) => "imports_left",
+            Error::Script(_) => "script",
+            Error::Multiple(_) => "multiple",
+        }
+    }
+
+    /// the file the error is about and the line in it, starting at 1, if the error has them
+    pub fn position(&self) -> (Option<&Path>, Option<usize>) {
+        match self {
+            Error::Circular {
+                location: Some(location),
+                ..
+            }
+            | Error::SelfImport(location)
+            | Error::DuplicateFunction { location, .. }
+            | Error::MissingInterpreter { location, .. }
+            | Error::RegionNotFound { location, .. }
+            | Error::EmptyImport { location, .. }
+            | Error::Unresolved { location, .. }
+            | Error::OutsideRoot { location, .. } => {
+                (Some(&location.path), Some(location.line_number + 1))
+            }
+            Error::IoWithPath { path, .. }
+            | Error::NotFound(path)
+            | Error::Encoding { path, .. }
+            | Error::AmbiguousCase { path, .. }
+            | Error::FileTooLarge { path, .. }
+            | Error::Preprocess { path, .. }
+            | Error::Archive { path, .. }
+            | Error::Permission { path, .. }
+            | Error::ConfigCycle(path) => (Some(path), None),
+            Error::Toml(err) => (None, err.line_col().map(|(line, _)| line + 1)),
+            _ => (None, None),
+        }
+    }
+
+    /// the exit code of the binary for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Multiple(errors) => errors.first().map_or(1, Error::exit_code),
+            Error::Warnings(_)
+            | Error::OutsideRoot { .. }
+            | Error::EmptyImport { .. }
+            | Error::Unresolved { .. } => 2,
+            Error::Script(code) => *code,
+            Error::Circular { .. } | Error::SelfImport(_) => 3,
+            Error::Io(_)
+            | Error::IoWithPath { .. }
+            | Error::NotFound(_)
+            | Error::Encoding { .. }
+            | Error::Archive { .. }
+            | Error::Permission { .. } => 4,
+            Error::Toml(_) | Error::Profile(_) | Error::ConfigCycle(_) => 5,
+            Error::UnsetVariable(_)
+            | Error::UnknownAlias(_)
+            | Error::MissingRoot
+            | Error::Condition(_)
+            | Error::AmbiguousCase { .. }
+            | Error::AmbiguousSource { .. }
+            | Error::OutputTooLarge(_)
+            | Error::FileTooLarge { .. }
+            | Error::Preprocess { .. }
+            | Error::DuplicateFunction { .. }
+            | Error::MissingInterpreter { .. }
+            | Error::RegionNotFound { .. }
+            | Error::ImportsLeft(_)
+            | Error::Glob { .. }
+            | Error::Shellcheck(_) => 1,
+        }
+    }
+
+    /// whether `--keep-going` can skip the import the error is about and continue with the next one
+    fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Error::IoWithPath { .. }
+                | Error::NotFound(_)
+                | Error::Encoding { .. }
+                | Error::AmbiguousSource { .. }
+                | Error::AmbiguousCase { .. }
+                | Error::FileTooLarge { .. }
+                | Error::Preprocess { .. }
+                | Error::Permission { .. }
+                | Error::EmptyImport { .. }
+                | Error::RegionNotFound { .. }
+                | Error::Unresolved { .. }
+                | Error::OutsideRoot { .. }
+                | Error::UnsetVariable(_)
+                | Error::UnknownAlias(_)
+                | Error::Glob { .. }
+                | Error::Condition(_)
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Error {
+        Error::Toml(err)
+    }
+}
+
+/// parse the command line arguments and run the bundler
+pub fn run() -> Result<(), Error> {
+    run_args(Args::from_args())
+}
+
+/// run the bundler with the parsed command line arguments, the entry point of the binary
+pub fn run_args(mut args: Args) -> Result<(), Error> {
+    if let Some(Command::Diff { old, new }) = &args.command {
+        let old_contents = std::fs::read_to_string(old)?;
+        let new_contents = std::fs::read_to_string(new)?;
+
+        print!(
+            "{}",
+            diff::unified(
+                &old.to_string_lossy(),
+                &old_contents,
+                &diff::marker_spans(&old_contents, &args.comment_prefix),
+                &new.to_string_lossy(),
+                &new_contents,
+                &diff::marker_spans(&new_contents, &args.comment_prefix),
+            )
+        );
+        return Ok(());
+    }
+
+    if args.config.is_none() && args.root_path.is_none() && args.only.is_none() {
+        args.config = default_config(&std::env::current_dir()?);
+    }
+    if let Some(config) = args.config.clone() {
+        let loaded = Config::load(&config)?;
+        args = loaded.into_args(args.profile.as_deref())?.merge(args);
+    } else if let Some(profile) = args.profile.take() {
+        return Err(Error::Profile(profile));
+    }
+    if let Some(only) = args.only.clone() {
+        args.root_path = Some(only);
+    }
+
+    match args.command.take() {
+        Some(Command::Run {
+            root,
+            interpreter,
+            arguments,
+        }) => {
+            args.root_path = Some(root.clone());
+            let bash_file = BashFile::resolve(root, &args)?;
+            if !args.quiet {
+                report::warnings(&bash_file.warnings);
+            }
+            write_manifest(&args, &bash_file)?;
+            return run_bundle(&bash_file.to_string(), &interpreter, &arguments);
+        }
+        Some(Command::CheckUnused { directory, roots }) => {
+            let mut reached = HashSet::new();
+            for root in roots {
+                args.root_path = Some(root.clone());
+                let file = BashFile::new(root)
+                    .load(&args, &DiskLoader)?
+                    .load_dependents(&args, &DiskLoader)?;
+                if !args.quiet {
+                    report::warnings(&file.warnings);
+                }
+                reached.extend(file.included_files());
+            }
+
+            let files = directory_files(&directory, true, &IgnoreFile::default(), &DiskLoader)?;
+            for file in files {
+                if !reached.contains(&std::fs::canonicalize(&file)?) {
+                    println!("{}", args.path_style.render(&file).display());
+                }
+            }
+            return Ok(());
+        }
+        Some(Command::Validate { bundle }) => {
+            // `source` lines in the bundle are relative to the bundle itself
+            args.root_path = Some(bundle.clone());
+            let file = BashFile::new(bundle).load(&args, &DiskLoader)?;
+            let left: Vec<_> = file.imports(&args, &DiskLoader).collect::<Result<_, _>>()?;
+            for import in &left {
+                println!(
+                    "{}:{}: {}",
+                    file.path.display(),
+                    import.line_number + 1,
+                    import.line
+                );
+            }
+            if !left.is_empty() {
+                return Err(Error::ImportsLeft(left.len()));
+            }
+            return Ok(());
+        }
+        Some(Command::Diff { .. }) | None => (),
+    }
+
+    if args.list_styles {
+        let root = args.root_path.clone().ok_or(Error::MissingRoot)?;
+        args.replace_comment = true;
+        args.replace_source = true;
+        let file = BashFile::new(root)
+            .load(&args, &DiskLoader)?
+            .load_dependents(&args, &DiskLoader)?;
+        println!("{}", file.style_counts());
+        return Ok(());
+    }
+
+    if let Some(x) = args.root_path.clone() {
+        if args.require_executable && !is_executable(&x) {
+            return Err(Error::Permission {
+                path: x,
+                permission: "executable",
+            });
+        }
+        let bash_file = BashFile::resolve(x, &args)?;
+        if !args.quiet {
+            report::warnings(&bash_file.warnings);
+        }
+        if args.fail_on_warning && !bash_file.warnings.is_empty() {
+            return Err(Error::Warnings(bash_file.warnings.len()));
+        }
+
+        if args.shellcheck {
+            shellcheck::run(&bash_file.to_string(), bash_file.spans())?;
+        }
+
+        if args.dry_run {
+            print_output_plan(&args, &bash_file);
+            return Ok(());
+        }
+        write_manifest(&args, &bash_file)?;
+        return write_output(&args, &bash_file);
+    }
+
+    Err(Error::MissingRoot)
+}
+
+/// the config found in the directory, when no config and root file are given
+fn default_config(directory: &Path) -> Option<PathBuf> {
+    DEFAULT_CONFIGS
+        .iter()
+        .map(|name| directory.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path).is_ok_and(|x| x.permissions().mode() & 0o111 != 0)
+}
+
+/// only unix has an executable permission
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.exists()
+}
+
+/// the programs a shebang line runs, the interpreter and the command of `/usr/bin/env`
+fn shebang_programs(line: &str) -> Vec<&str> {
+    let mut words = match line.strip_prefix("#!") {
+        Some(rest) => rest.split_whitespace(),
+        None => return Vec::new(),
+    };
+    let interpreter = match words.next() {
+        Some(interpreter) => interpreter,
+        None => return Vec::new(),
+    };
+    if interpreter.rsplit('/').next() != Some("env") {
+        return vec![interpreter];
+    }
+
+    // the options and variable assignments of `env` come before the command
+    let command = words.find(|x| !x.starts_with('-') && !x.contains('='));
+    std::iter::once(interpreter).chain(command).collect()
+}
+
+/// whether the program is an executable file, a name without a `/` is looked up on the `PATH`
+fn is_installed(program: &str) -> bool {
+    if program.contains('/') {
+        let path = Path::new(program);
+        return path.is_file() && is_executable(path);
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            let path = dir.join(program);
+            path.is_file() && is_executable(&path)
+        })
+    })
+}
+
+/// write the bundle to the configured output file, or stdout
+fn write_output(args: &Args, file: &BashFile) -> Result<(), Error> {
+    match args.output_path() {
+        Some(path) => {
+            let _lock = if args.concurrency_safe_output {
+                lock_output(&path)?
+            } else {
+                None
+            };
+            if args.if_changed && is_unchanged(&path, file) {
+                eprintln!("unchanged {}", path.display());
+                return Ok(());
+            }
+            write_atomic(&path, file)
+        }
+        None => {
+            print!("{}", file);
+            Ok(())
+        }
+    }
+}
+
+/// print where `write_output` would write the bundle to, and how large it is
+fn print_output_plan(args: &Args, file: &BashFile) {
+    if let (Some(path), Some(manifest)) = (&args.manifest, &file.manifest) {
+        println!("would write {} bytes to {}", manifest.len(), path.display());
+    }
+    let size = file.contents.as_ref().map_or(0, |x| x.len());
+    match args.output_path() {
+        Some(path) if args.if_changed && is_unchanged(&path, file) => {
+            println!("would leave {} unchanged", path.display())
+        }
+        Some(path) => println!("would write {} bytes to {}", size, path.display()),
+        None => println!("would write {} bytes to stdout", size),
+    }
+}
+
+/// write the manifest of the files included in the bundle, with `--manifest`
+fn write_manifest(args: &Args, file: &BashFile) -> Result<(), Error> {
+    match (&args.manifest, &file.manifest) {
+        (Some(path), Some(manifest)) => {
+            std::fs::write(path, manifest).map_err(|source| Error::IoWithPath {
+                path: path.clone(),
+                source,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// whether the file at the path already has the contents of the bundle
+fn is_unchanged(path: &Path, file: &BashFile) -> bool {
+    let contents = file.contents.as_deref().unwrap_or_default();
+    std::fs::read(path).is_ok_and(|x| x == contents.as_bytes())
+}
+
+/// the path of the hidden `.<name>.<extension>` file next to the path
+fn hidden_sibling(path: &Path, extension: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("output"));
+    path.with_file_name(format!(".{}.{}", file_name, extension))
+}
+
+/// take an exclusive lock on a lock file next to the path, the lock is released when the file is dropped
+///
+/// returns `None` when the file system does not support locking, the output is then written without a lock
+fn lock_output(path: &Path) -> Result<Option<std::fs::File>, Error> {
+    let lock_path = hidden_sibling(path, "lock");
+    let with_path = |source| Error::IoWithPath {
+        path: lock_path.clone(),
+        source,
+    };
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(with_path)?;
+    match file.lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+            eprintln!(
+                "warning: could not lock {}, writing the output without a lock: {}",
+                lock_path.display(),
+                err
+            );
+            Ok(None)
+        }
+        Err(err) => Err(with_path(err)),
+    }
+}
+
+/// write to a temporary file next to the path and rename it over the path, so readers never see a partial file
+///
+/// falls back to writing the path directly if the rename fails
+fn write_atomic(path: &Path, file: &BashFile) -> Result<(), Error> {
+    let with_path = |source| Error::IoWithPath {
+        path: path.to_path_buf(),
+        source,
+    };
+    let output = file.contents.as_deref().unwrap_or_default();
+    let write = |path: &Path| std::fs::write(path, output);
+    let temp = hidden_sibling(path, &format!("{}.tmp", std::process::id()));
+
+    write(&temp).map_err(with_path)?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        let _ = std::fs::set_permissions(&temp, metadata.permissions());
+    }
+    if let Err(err) = std::fs::rename(&temp, path) {
+        let _ = std::fs::remove_file(&temp);
+        eprintln!(
+            "warning: could not replace {} atomically, writing it directly: {}",
+            path.display(),
+            err
+        );
+        write(path).map_err(with_path)?;
+    }
+    Ok(())
+}
+
+/// the output of the shell command with the contents as its input
+fn preprocess(command: &str, path: &Path, contents: Vec<u8>) -> Result<Vec<u8>, Error> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("BASH_BUNDLER_FILE", path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+
+    // written from another thread, so a command that writes before reading all input does not block
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || stdin.write_all(&contents));
+    let output = child.wait_with_output()?;
+    // a command that does not read its input closes the pipe early, that is not an error
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(Error::Preprocess {
+            path: path.to_path_buf(),
+            message: match output.status.code() {
+                Some(code) => format!("`{}` exited with code {}", command, code),
+                None => format!("`{}` was terminated", command),
+            },
+        });
+    }
+    Ok(output.stdout)
+}
+
+/// execute the bundle from a temporary file with the interpreter, removing the file afterwards
+fn run_bundle(output: &str, interpreter: &str, arguments: &[String]) -> Result<(), Error> {
+    let temp = write_temp_script(output)?;
+
+    let status = std::process::Command::new(interpreter)
+        .arg(&temp)
+        .args(arguments)
+        .status();
+    let _ = std::fs::remove_file(&temp);
+
+    match status?.code() {
+        Some(0) => Ok(()),
+        // killed by a signal
+        code => Err(Error::Script(code.unwrap_or(1))),
+    }
+}
+
+/// write the bundle to a new file with a random name in the temp directory,
+/// so another user cannot put a file there before it
+fn write_temp_script(output: &str) -> Result<PathBuf, Error> {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    use std::io::Write;
+
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |x| x.as_nanos()),
+        );
+        let temp = std::env::temp_dir().join(format!("bash_bundler.{:016x}.sh", hasher.finish()));
+
+        let written = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)
+            .and_then(|mut file| file.write_all(output.as_bytes()));
+        match written {
+            Ok(()) => return Ok(temp),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(source) => return Err(Error::IoWithPath { path: temp, source }),
+        }
+    }
+}
+
+fn existing_path(path: &str) -> Result<PathBuf, Error> {
+    let path = PathBuf::from(path);
+    if !path.exists() {
+        return Err(Error::NotFound(path));
+    }
+
+    Ok(path)
+}
+
+/// whether the import path is a glob, like `./plugins/*.sh` or `./plugins/**/*.sh`
+fn is_glob(text: &str) -> bool {
+    text.contains(['*', '?', '['])
+}
+
+/// the shell files matching the glob sorted by path, `**` matches any number of directories,
+/// without the files and directories in the ignore file
+fn glob_files(
+    pattern: &Path,
+    ignore: &IgnoreFile,
+    loader: &dyn SourceLoader,
+) -> Result<Vec<PathBuf>, Error> {
+    // without the `.` components in the middle, the walked paths do not have them either
+    let pattern: PathBuf = pattern.components().collect();
+    let matcher = Pattern::new(&pattern.to_string_lossy()).map_err(|err| Error::Glob {
+        pattern: pattern.clone(),
+        message: err.to_string(),
+    })?;
+    let base: PathBuf = pattern
+        .components()
+        .take_while(|x| !is_glob(&x.as_os_str().to_string_lossy()))
+        .collect();
+    let base = if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base
+    };
+
+    let mut files = Vec::new();
+    if loader.is_dir(&base) {
+        walk_glob(
+            &base,
+            &matcher,
+            ignore,
+            loader,
+            &mut files,
+            &mut HashSet::new(),
+        )?;
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// collect the shell files under the directory matching the glob
+///
+/// `visited` has the canonical paths of the walked directories, so a symlink to a parent directory does not loop
+fn walk_glob(
+    directory: &Path,
+    matcher: &Pattern,
+    ignore: &IgnoreFile,
+    loader: &dyn SourceLoader,
+    files: &mut Vec<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), Error> {
+    if !visited.insert(loader.canonicalize(directory)?) {
+        return Ok(());
+    }
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..glob::MatchOptions::new()
+    };
+
+    for path in loader.read_dir(directory)? {
+        let is_dir = loader.is_dir(&path);
+        if ignore.is_ignored(&path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            walk_glob(&path, matcher, ignore, loader, files, visited)?;
+        } else if ALLOWED_EXTENSIONS.contains(&path.extension().and_then(|x| x.to_str()))
+            && matcher.matches_path_with(&path, options)
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// the shell files in the directory sorted by path, including subdirectories if `recursive`,
+/// without the files and directories in the ignore file
+fn directory_files(
+    directory: &Path,
+    recursive: bool,
+    ignore: &IgnoreFile,
+    loader: &dyn SourceLoader,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    walk_directory(
+        directory,
+        recursive,
+        ignore,
+        loader,
+        &mut files,
+        &mut HashSet::new(),
+    )?;
+    files.sort();
+
+    Ok(files)
+}
+
+/// collect the shell files in the directory
+///
+/// `visited` has the canonical paths of the walked directories, so a symlink to a parent directory does not loop
+fn walk_directory(
+    directory: &Path,
+    recursive: bool,
+    ignore: &IgnoreFile,
+    loader: &dyn SourceLoader,
+    files: &mut Vec<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), Error> {
+    if !visited.insert(loader.canonicalize(directory)?) {
+        return Ok(());
+    }
+    for path in loader.read_dir(directory)? {
+        let is_dir = loader.is_dir(&path);
+        if ignore.is_ignored(&path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            if recursive {
+                walk_directory(&path, recursive, ignore, loader, files, visited)?;
+            }
+        } else if ALLOWED_EXTENSIONS.contains(&path.extension().and_then(|x| x.to_str())) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// returns the function name if the line starts a function definition
+///
+/// supports both the `name() {` and `function name {` syntax
+fn function_name(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let (name, rest) = match line.strip_prefix("function ") {
+        Some(x) => {
+            let x = x.trim_start();
+            let end = x
+                .find(|c: char| c.is_whitespace() || c == '(' || c == '{')
+                .unwrap_or(x.len());
+            (&x[..end], &x[end..])
+        }
+        None => {
+            let end = line.find('(')?;
+            let rest = line[end + 1..].trim_start().strip_prefix(')')?;
+            (line[..end].trim_end(), rest)
+        }
+    };
+
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.'))
+    {
+        return None;
+    }
+
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('{') || rest.starts_with('(') || rest.starts_with('#') {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// the variant of the `import` keyword
+enum ImportMarker {
+    /// `import`, inlined unless the comment syntax is disabled
+    Plain,
+    /// `import!`, always inlined
+    Forced,
+    /// `import?`, only inlined with `--optional-imports`
+    Optional,
+}
+
+/// strips the `import` keyword following the comment prefix, with or without a space in between
+///
+/// the keyword has to be followed by whitespace, so `#important` is not an import
+fn strip_import_keyword(input: &str) -> Option<&str> {
+    strip_marked_import_keyword(input).map(|(_, rest)| rest)
+}
+
+/// strips the `import`, `import!` or `import?` keyword, returning which one it is
+fn strip_marked_import_keyword(input: &str) -> Option<(ImportMarker, &str)> {
+    let rest = input.strip_prefix(' ').unwrap_or(input);
+    let rest = rest.strip_prefix("import")?;
+    let (marker, rest) = if let Some(rest) = rest.strip_prefix('!') {
+        (ImportMarker::Forced, rest)
+    } else if let Some(rest) = rest.strip_prefix('?') {
+        (ImportMarker::Optional, rest)
+    } else {
+        (ImportMarker::Plain, rest)
+    };
+    if rest.starts_with(char::is_whitespace) {
+        Some((marker, rest.trim_start()))
+    } else {
+        None
+    }
+}
+
+/// the import line commented out, with the `(inlined)` suffix
+fn source_ref(import: &ImportStatement, config: &Args) -> String {
+    match import.style {
+        ImportStyle::Comment => format!("{}{}", import.line, SOURCE_REF_SUFFIX),
+        ImportStyle::Source => {
+            let text = import.line.trim_start();
+            let indent = &import.line[..import.line.len() - text.len()];
+            format!(
+                "{}{} {}{}",
+                indent, config.comment_prefix, text, SOURCE_REF_SUFFIX
+            )
+        }
+    }
+}
+
+/// the kept import line and the markers around its contents, a `source` line is commented out
+fn directive(import: &ImportStatement, config: &Args) -> (String, String, String) {
+    let text = import.line.trim_start();
+    let indent = &import.line[..import.line.len() - text.len()];
+    let line = match import.style {
+        ImportStyle::Comment => import.line.clone(),
+        ImportStyle::Source => format!("{}{} {}", indent, config.comment_prefix, text),
+    };
+    let text = marker_text(import, config);
+    let marker = |kind| format!("{}{} ({} {})", indent, config.comment_prefix, kind, text);
+    (line, marker("begin"), marker("end"))
+}
+
+/// the import in the markers around inlined contents, the text as typed or the resolved path in the `--path-style`
+fn marker_text<'a>(import: &'a ImportStatement, config: &Args) -> Cow<'a, str> {
+    if config.path_style == PathStyle::AsTyped {
+        return Cow::Borrowed(&import.text);
+    }
+    let path = config.path_style.render(&import.path).display().to_string();
+    match &import.region {
+        Some(region) => Cow::Owned(format!("{}#{}", path, region)),
+        None => Cow::Owned(path),
+    }
+}
+
+/// the first line joined with the comment lines continuing its import path, and the number of lines joined
+///
+/// with `--import-continuation` a comment import ending in `\` continues on the next comment line,
+/// without the comment prefix and the leading whitespace of that line
+fn join_continuation<'a>(lines: &[&'a str], config: &Args) -> (Cow<'a, str>, usize) {
+    let first = lines[0];
+    let is_import = first
+        .strip_prefix(config.comment_prefix.as_str())
+        .and_then(strip_import_keyword)
+        .is_some();
+    if !config.import_continuation || !is_import || !first.ends_with('\\') {
+        return (Cow::Borrowed(first), 0);
+    }
+
+    let mut joined = String::from(first);
+    let mut continued = 0;
+    while joined.ends_with('\\') {
+        let next = lines
+            .get(continued + 1)
+            .and_then(|x| x.trim_start().strip_prefix(config.comment_prefix.as_str()));
+        match next {
+            Some(next) => {
+                joined.pop();
+                joined.push_str(next.trim_start());
+                continued += 1;
+            }
+            None => break,
+        }
+    }
+    (Cow::Owned(joined), continued)
+}
+
+/// whether the line is the `# (begin ./file.sh)` marker of a kept import line
+fn is_begin_marker(line: &str, config: &Args) -> bool {
+    line.trim_start()
+        .strip_prefix(config.comment_prefix.as_str())
+        .is_some_and(|x| x.starts_with(" (begin "))
+}
+
+/// strips the `source` or `.` builtin, which has to be followed by whitespace
+fn strip_source_prefix(input: &str) -> Option<&str> {
+    ["source", "."].iter().find_map(|builtin| {
+        let rest = input.strip_prefix(builtin)?;
+        if rest.starts_with(char::is_whitespace) {
+            Some(rest.trim_start())
+        } else {
+            None
+        }
+    })
+}
+
+/// evaluates the `$VAR == value` or `$VAR != value` condition of an import, unset variables are empty
+fn import_condition(condition: &str) -> Result<bool, Error> {
+    let invalid = || Error::Condition(String::from(condition.trim()));
+    let (name, operator, value) = match condition.split_whitespace().collect::<Vec<_>>()[..] {
+        [name, operator, value] => (name, operator, value),
+        _ => return Err(invalid()),
+    };
+
+    let name = name.strip_prefix('$').ok_or_else(invalid)?;
+    let name = match name.strip_prefix('{') {
+        Some(braced) => braced.strip_suffix('}').ok_or_else(invalid)?,
+        None => name,
+    };
+    if name.is_empty()
+        || name.starts_with(|c: char| c.is_ascii_digit())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(invalid());
+    }
+
+    let actual = std::env::var(name).unwrap_or_default();
+    match operator {
+        "==" => Ok(actual == unquote(value)),
+        "!=" => Ok(actual != unquote(value)),
+        _ => Err(invalid()),
+    }
+}
+
+/// the file in the same directory whose name matches the file name of the path, ignoring case
+///
+/// fails if multiple files match
+fn case_insensitive_match(path: &Path) -> Result<Option<PathBuf>, Error> {
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_lowercase(),
+        None => return Ok(None),
+    };
+    let entries = match std::fs::read_dir(parent_dir(path)) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(None),
+    };
+
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|x| {
+            x.file_name()
+                .is_some_and(|x| x.to_string_lossy().to_lowercase() == name)
+        })
+        .collect();
+    matches.sort();
+
+    match matches.len() {
+        0 => Ok(None),
+        1 => Ok(matches.pop()),
+        _ => Err(Error::AmbiguousCase {
+            path: path.to_path_buf(),
+            matches,
+        }),
+    }
+}
+
+/// remove the blank lines at the start and end of the contents
+fn trim_blank_lines(contents: &str, spans: &mut Vec<SourceSpan>) -> String {
+    let lines: Vec<&str> = contents.split('\n').collect();
+    let start = lines
+        .iter()
+        .position(|x| !x.trim().is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|x| !x.trim().is_empty())
+        .map_or(start, |x| x + 1);
+
+    for index in (end..lines.len()).rev() {
+        source_map::remove_line(spans, index);
+    }
+    for _ in 0..start {
+        source_map::remove_line(spans, 0);
+    }
+    lines[start..end].join("\n")
+}
+
+/// put exactly one blank line between inlined imports that are only separated by blank lines
+///
+/// the entries are the lines of a file with their line number, the inlined imports are keys of `inlined`
+fn separate_inlined<T>(
+    entries: Vec<(Option<usize>, String)>,
+    inlined: &HashMap<usize, T>,
+) -> Vec<(Option<usize>, String)> {
+    let mut output = Vec::with_capacity(entries.len());
+    let mut blank = Vec::new();
+    let mut previous_inlined = false;
+    for (index, line) in entries {
+        let is_inlined = index.is_some_and(|x| inlined.contains_key(&x));
+        if is_inlined && line.is_empty() {
+            continue;
+        }
+        if !is_inlined && line.trim().is_empty() {
+            blank.push((index, line));
+            continue;
+        }
+
+        if is_inlined && previous_inlined {
+            blank.clear();
+            output.push((None, String::new()));
+        }
+        output.append(&mut blank);
+        output.push((index, line));
+        previous_inlined = is_inlined;
+    }
+    output.append(&mut blank);
+    output
+}
+
+/// the directory containing the file, `.` for a bare file name
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if parent != Path::new("") => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// splits the path of a `source` line, quoted or up to the first whitespace or operator, from the rest of the line
+fn split_source_path(input: &str) -> (&str, &str) {
+    for quote in ['"', '\''] {
+        if let Some(rest) = input.strip_prefix(quote) {
+            return match rest.find(quote) {
+                Some(end) => (&rest[..end], &rest[end + 1..]),
+                // left as is, so it does not resolve
+                None => (input, ""),
+            };
+        }
+    }
+
+    let end = input
+        .find(|c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|'))
+        .unwrap_or(input.len());
+    (&input[..end], &input[end..])
+}
+
+/// the command following the `source` path, as a line that runs after the inlined contents
+///
+/// arguments and comments are dropped, `&& cmd` and `|| cmd` use the exit code of the inlined contents,
+/// the inlined contents are not run in the background with `& cmd`
+fn trailing_command(rest: &str) -> Option<String> {
+    let start = rest.find([';', '&', '|', '#'])?;
+    let rest = &rest[start..];
+    if rest.starts_with("&&") || rest.starts_with("||") {
+        return Some(format!("(exit $?) {}", rest.trim_end()));
+    }
+
+    let command = rest.strip_prefix([';', '&'])?.trim();
+    if command.is_empty() {
+        None
+    } else {
+        Some(String::from(command))
+    }
+}
+
+/// strips matching surrounding single or double quotes
+fn unquote(input: &str) -> &str {
+    for quote in &['"', '\''] {
+        if input.len() >= 2 && input.starts_with(*quote) && input.ends_with(*quote) {
+            return &input[1..input.len() - 1];
+        }
+    }
+    input
+}
+
+/// splits the `#name` of a region from the import path, `./lib.sh#name` imports the `name` region of `./lib.sh`
+fn split_region(input: &str) -> (&str, Option<&str>) {
+    match input.rsplit_once('#') {
+        Some((path, name))
+            if !path.is_empty()
+                && !name.is_empty()
+                && !name.contains(|c: char| c == '/' || c.is_whitespace()) =>
+        {
+            (path, Some(name))
+        }
+        _ => (input, None),
+    }
+}
+
+/// the lines of the region between the `# region name` and `# endregion name` markers,
+/// with the index of its first line
+fn region_lines<'a>(contents: &'a str, name: &str, config: &Args) -> Option<(usize, Vec<&'a str>)> {
+    let is_marker = |line: &str, keyword: &str| {
+        line.trim_start()
+            .strip_prefix(config.comment_prefix.as_str())
+            .and_then(|x| x.trim_start().strip_prefix(keyword))
+            .and_then(|x| x.strip_prefix(char::is_whitespace))
+            .is_some_and(|x| x.trim() == name)
+    };
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let start = lines.iter().position(|x| is_marker(x, "region"))? + 1;
+    let length = lines[start..]
+        .iter()
+        .position(|x| is_marker(x, "endregion"))?;
+    Some((start, lines[start..start + length].to_vec()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportStyle {
+    Comment,
+    Source,
+}
+
+impl std::fmt::Display for ImportStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportStyle::Comment => write!(f, "comment"),
+            ImportStyle::Source => write!(f, "source"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImportStatement {
+    line_number: usize,
+    line: String,
+    text: String,
+    path: PathBuf,
+    style: ImportStyle,
+    resolved: Vec<BashFile>,
+    unresolved: Option<Unresolved>,
+    /// the command after the `source` path, like `|| exit 1`, kept after the inlined contents
+    trailing: Option<String>,
+    /// the number of lines after the import line that continue its path
+    continued: usize,
+    /// the name of the region to import, from the `#name` after the path
+    region: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// the reason an import could not be resolved
+pub enum Unresolved {
+    NotFound,
+    Extension,
+    NoMatch,
+    UnsetVariable,
+}
+
+impl std::fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unresolved::NotFound => write!(f, "file not found"),
+            Unresolved::Extension => write!(f, "not a shell file extension"),
+            Unresolved::NoMatch => write!(f, "no shell files match the pattern"),
+            Unresolved::UnsetVariable => {
+                write!(f, "an environment variable in the path is not set")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// a non fatal problem found while bundling
+pub struct Warning {
+    location: Location,
+    message: String,
+}
+
+impl ImportStatement {
+    /// the line of the import statement in its file, starting at 0
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// the path as written in the import statement
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// the path the import resolves to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// the syntax of the import statement
+    pub fn style(&self) -> ImportStyle {
+        self.style
+    }
+
+    /// why the import could not be resolved, `None` if it is resolved
+    pub fn unresolved(&self) -> Option<Unresolved> {
+        self.unresolved
+    }
+
+    /// the name of the region that is imported, `None` if the whole file is imported
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// the location of the import statement in the given file
+    pub fn location(&self, file: &Path) -> Location {
+        Location {
+            path: file.to_path_buf(),
+            line_number: self.line_number,
+            line: self.line.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// a line in a source file, used for reporting
+pub struct Location {
+    path: PathBuf,
+    line_number: usize,
+    line: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+/// statistics about a bundle
+pub struct Stats {
+    files: usize,
+    imports: usize,
+    input_lines: usize,
+    output_lines: usize,
+    max_depth: usize,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "files included: {}", self.files)?;
+        writeln!(f, "imports resolved: {}", self.imports)?;
+        writeln!(f, "input lines: {}", self.input_lines)?;
+        writeln!(f, "output lines: {}", self.output_lines)?;
+        write!(f, "max nesting depth: {}", self.max_depth)
+    }
+}
+
+#[derive(Debug)]
+/// how long the phases of bundling took, printed with `--timings`
+struct Timings {
+    load: Duration,
+    resolve: Duration,
+    /// the files that took the longest to read, slowest first
+    slowest_reads: Vec<(PathBuf, Duration)>,
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "load: {:.3?}", self.load)?;
+        write!(f, "resolve: {:.3?}", self.resolve)?;
+        if !self.slowest_reads.is_empty() {
+            write!(f, "\nslowest reads:")?;
+        }
+        for (path, duration) in &self.slowest_reads {
+            write!(f, "\n  {:.3?} {}", duration, path.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+/// the number of imports of each syntax in a dependency tree
+pub struct StyleCounts {
+    comment: usize,
+    source: usize,
+}
+
+impl std::fmt::Display for StyleCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "comment imports (`# import ./file.sh`): {}",
+            self.comment
+        )?;
+        writeln!(f, "source imports (`source ./file.sh`): {}", self.source)?;
+        match (self.comment > 0, self.source > 0) {
+            (true, true) => write!(f, "both syntaxes are used, use `--auto`"),
+            (false, true) => write!(f, "use `--enable-source --disable-comment`"),
+            (true, false) => write!(f, "the default flags resolve these imports"),
+            (false, false) => write!(f, "no imports found"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// how a line is treated by `--main-guard`
+enum Guard {
+    /// a top level statement of the root file
+    Statement,
+    /// a blank or comment line, guarded only between statements
+    Neutral,
+    /// a shebang, function definition or inlined line
+    Outside,
+}
+
+#[derive(Debug, Default)]
+/// container for a bash file
+pub struct BashFile {
+    path: PathBuf,
+    contents: Option<String>,
+    dependents: Vec<ImportStatement>,
+    nested: usize,
+    crlf: bool,
+    trailing_newline: bool,
+    /// the canonical path, so a symlink and its target are the same file
+    identity: PathBuf,
+    /// identities of the files importing this file
+    ancestors: Vec<PathBuf>,
+    warnings: Vec<Warning>,
+    /// where the lines of the resolved contents come from
+    spans: Vec<SourceSpan>,
+    /// SHA-256 of the file as loaded from disk, in hex
+    hash: String,
+    /// size in bytes of the file as loaded from disk
+    size: usize,
+    /// the file is loaded and resolved once at another import, that result is reused
+    memoized: bool,
+    /// how long reading the file took
+    read_time: Duration,
+    /// the region of the file that is imported, its contents are only the lines of the region
+    region: Option<String>,
+    /// the number of lines in the file before the contents, the lines before the region
+    first_line: usize,
+    /// the manifest of the loaded files with `--manifest`, written next to the bundle
+    manifest: Option<String>,
+}
+
+/// files with the same identity, region and number of levels left to inline resolve to the same contents
+type MemoKey = (PathBuf, Option<String>, Option<usize>);
+
+/// the state shared while loading the imports of one root file
+struct LoadState {
+    /// the files whose imports are loaded already, they are not loaded again
+    loaded: HashSet<MemoKey>,
+    /// the recoverable errors collected with `--keep-going`
+    errors: Vec<Error>,
+    /// the ignore file in the directory of the root file, for glob and directory imports
+    ignore: IgnoreFile,
+}
+
+fn memo_key(identity: &Path, region: Option<&str>, nested: usize, config: &Args) -> MemoKey {
+    let levels_left = config.max_depth().map(|x| x.saturating_sub(nested));
+    (
+        identity.to_path_buf(),
+        region.map(String::from),
+        levels_left,
+    )
+}
+
+/// collect a recoverable error with `--keep-going`, other errors are returned
+fn keep_going(err: Error, config: &Args, errors: &mut Vec<Error>) -> Result<(), Error> {
+    if config.keep_going && err.is_recoverable() {
+        errors.push(err);
+        return Ok(());
+    }
+    Err(err)
+}
+
+impl std::fmt::Display for BashFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.contents {
+            None => write!(f, ""),
+            Some(contents) => write!(f, "{}", contents),
+        }
+    }
+}
+
+impl BashFile {
+    /// loads, imports and resolves the file from disk
+    pub fn resolve(path: PathBuf, config: &Args) -> Result<Self, Error> {
+        match &config.archive {
+            Some(archive) => {
+                let loader = ArchiveLoader::open(archive, &parent_dir(&path))?;
+                Self::resolve_with_loader(path, config, &loader)
+            }
+            None => Self::resolve_with_loader(path, config, &DiskLoader),
+        }
+    }
+
+    /// loads, imports and resolves the file, reading the files with the loader
+    pub fn resolve_with_loader(
+        path: PathBuf,
+        config: &Args,
+        loader: &dyn SourceLoader,
+    ) -> Result<Self, Error> {
+        config.log(1, format_args!("load {}", path.display()));
+        config.check_readable(&path, loader)?;
+        let start = Instant::now();
+        let file = BashFile::new(path)
+            .load(config, loader)?
+            .load_dependents(config, loader)?;
+        let load_time = start.elapsed();
+        let slowest_reads = if config.timings && !config.quiet {
+            Some(file.slowest_reads(SLOWEST_READS))
+        } else {
+            None
+        };
+        let mut stats = if config.stats && !config.quiet {
+            Some(file.stats())
+        } else {
+            None
+        };
+        if config.debug_tree {
+            eprint!("{}", file.tree(config.path_style));
+        }
+        if config.explain {
+            eprint!("{}", file.explain(config.path_style));
+        }
+        let manifest = config
+            .manifest
+            .as_ref()
+            .map(|_| file.manifest(config.path_style));
+
+        let start = Instant::now();
+        let file = match config.mode {
+            Mode::Inline => file.resolve_dependents(config)?,
+            Mode::Flatten => file.flatten_dependents(config),
+        };
+        let resolve_time = start.elapsed();
+        if let Some(slowest_reads) = slowest_reads {
+            let timings = Timings {
+                load: load_time,
+                resolve: resolve_time,
+                slowest_reads,
+            };
+            eprintln!("{}", timings);
+        }
+        let mut file = file
+            .apply_strip_toplevel(config)
+            .apply_compat_lint(config)
+            .apply_normalize_functions(config)?
+            .apply_squeeze_blank(config)
+            .apply_reindent(config)
+            .apply_call_main(config)
+            .apply_main_guard(config)
+            .apply_shebang(config)
+            .apply_injected(config)?
+            .apply_no_shebang(config)
+            .apply_banner(config)?
+            .apply_interpreter_check(config)?
+            .apply_trailing_newline(config);
+        config.check_output_size(file.contents.as_ref().map_or(0, String::len))?;
+
+        if let Some(stats) = stats.as_mut() {
+            stats.output_lines = file.lines().count();
+            eprintln!("{}", stats);
+        }
+
+        file.manifest = manifest;
+        Ok(file.apply_line_ending(config))
+    }
+
+    /// loads, imports and resolves the file, returning the output with the source of its lines
+    pub fn resolve_with_map(
+        path: PathBuf,
+        config: &Args,
+    ) -> Result<(String, Vec<SourceSpan>), Error> {
+        let file = BashFile::resolve(path, config)?;
+        let output = file.to_string();
+
+        Ok((output, file.spans))
+    }
+
+    /// where the lines of the resolved contents come from
+    pub fn spans(&self) -> &[SourceSpan] {
+        &self.spans
+    }
+
+    /// the source file and line of a line in the output, using the map of `resolve_with_map`
+    ///
+    /// line numbers start at 0, lines that are not from a source file, like the banner, give `None`
+    pub fn output_line_to_source(
+        map: &[SourceSpan],
+        output_line: usize,
+    ) -> Option<(PathBuf, usize)> {
+        source_map::find(map, output_line).map(|(path, line)| (path.to_path_buf(), line))
+    }
+
+    /// collect the statistics of the loaded dependency tree
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        let mut seen = HashSet::new();
+        self.collect_stats(&mut stats, &mut seen);
+        stats
+    }
+
+    /// list the included files as `<sha256> <size> <path>` lines, sorted by path
+    pub fn manifest(&self, style: PathStyle) -> String {
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        self.collect_manifest(&mut entries, &mut seen, style);
+        entries.sort_by(|a, b| a.2.cmp(&b.2));
+
+        entries
+            .into_iter()
+            .map(|(hash, size, path)| format!("{} {} {}\n", hash, size, path.display()))
+            .collect()
+    }
+
+    /// the paths of the files that took the longest to read, with their read time, slowest first
+    fn slowest_reads(&self, count: usize) -> Vec<(PathBuf, Duration)> {
+        let mut reads = Vec::new();
+        self.collect_read_times(&mut reads, &mut HashSet::new());
+        reads.sort_by_key(|x| std::cmp::Reverse(x.1));
+        reads.truncate(count);
+        reads
+    }
+
+    fn collect_read_times(
+        &self,
+        reads: &mut Vec<(PathBuf, Duration)>,
+        seen: &mut HashSet<PathBuf>,
+    ) {
+        if seen.insert(self.identity.clone()) {
+            reads.push((self.path.clone(), self.read_time));
+        }
+
+        for import in &self.dependents {
+            for dep in &import.resolved {
+                dep.collect_read_times(reads, seen);
+            }
+        }
+    }
+
+    fn collect_manifest<'a>(
+        &'a self,
+        entries: &mut Vec<(&'a str, usize, PathBuf)>,
+        seen: &mut HashSet<PathBuf>,
+        style: PathStyle,
+    ) {
+        if seen.insert(self.identity.clone()) {
+            // drops the `.` components in the middle, `./a/./b.sh` becomes `./a/b.sh`
+            let path = style.render(&self.path).components().collect();
+            entries.push((&self.hash, self.size, path));
+        }
+
+        for import in &self.dependents {
+            for dep in &import.resolved {
+                dep.collect_manifest(entries, seen, style);
+            }
+        }
+    }
+
+    /// the loaded files and their imports as an indented tree, one line per file or import
+    pub fn tree(&self, style: PathStyle) -> String {
+        let mut output = String::new();
+        self.write_tree(&mut output, 0, style);
+        output
+    }
+
+    /// describe every import in the loaded dependency tree, with the files it resolved to or the path that was checked
+    pub fn explain(&self, style: PathStyle) -> String {
+        let mut output = String::new();
+        self.write_explain(&mut output, &mut HashSet::new(), style);
+        output
+    }
+
+    fn write_explain(&self, output: &mut String, seen: &mut HashSet<PathBuf>, style: PathStyle) {
+        if !seen.insert(self.identity.clone()) {
+            return;
+        }
+
+        for import in &self.dependents {
+            let location = format!(
+                "{}:{}: `{}`",
+                style.render(&self.path).display(),
+                import.line_number + 1,
+                import.text
+            );
+            match import.unresolved {
+                Some(unresolved) => output.push_str(&format!(
+                    "{} is not resolved, {}: {}\n",
+                    location,
+                    unresolved,
+                    style.render(&import.path).display()
+                )),
+                None => {
+                    let paths: Vec<_> = import
+                        .resolved
+                        .iter()
+                        .map(|x| style.render(&x.path).display().to_string())
+                        .collect();
+                    output.push_str(&format!("{} resolved to {}\n", location, paths.join(", ")));
+                }
+            }
+        }
+
+        for dep in self.dependents.iter().flat_map(|x| &x.resolved) {
+            dep.write_explain(output, seen, style);
+        }
+    }
+
+    fn write_tree(&self, output: &mut String, depth: usize, style: PathStyle) {
+        output.push_str(&format!(
+            "{}{}{}\n",
+            "    ".repeat(depth),
+            style.render(&self.path).display(),
+            if self.memoized { " (see above)" } else { "" }
+        ));
+        for import in &self.dependents {
+            let unresolved = match import.unresolved {
+                Some(unresolved) => format!(", {}", unresolved),
+                None => String::new(),
+            };
+            output.push_str(&format!(
+                "{}  {}: {} ({}{})\n",
+                "    ".repeat(depth),
+                import.line_number + 1,
+                import.text,
+                import.style,
+                unresolved
+            ));
+            for dep in &import.resolved {
+                dep.write_tree(output, depth + 1, style);
+            }
+        }
+    }
+
+    /// the canonical paths of the file and every file it imports, directly or indirectly
+    pub fn included_files(&self) -> HashSet<PathBuf> {
+        let mut seen = HashSet::new();
+        self.collect_included_files(&mut seen);
+        seen
+    }
+
+    fn collect_included_files(&self, seen: &mut HashSet<PathBuf>) {
+        if seen.insert(self.identity.clone()) {
+            for import in &self.dependents {
+                for dep in &import.resolved {
+                    dep.collect_included_files(seen);
+                }
+            }
+        }
+    }
+
+    /// the imports of the file and of every file it imports, depth first in the order they appear
+    ///
+    /// the tree has to be loaded with `load_dependents`, a file imported more than once only yields its imports the first time
+    pub fn all_imports<'a>(&'a self) -> Box<dyn Iterator<Item = &'a ImportStatement> + 'a> {
+        Box::new(self.dependents.iter().flat_map(|import| {
+            std::iter::once(import).chain(import.resolved.iter().flat_map(|x| x.all_imports()))
+        }))
+    }
+
+    /// count the imports of each syntax in the loaded dependency tree, every file is counted once
+    pub fn style_counts(&self) -> StyleCounts {
+        let mut counts = StyleCounts::default();
+        let mut seen = HashSet::new();
+        self.collect_style_counts(&mut counts, &mut seen);
+        counts
+    }
+
+    fn collect_style_counts(&self, counts: &mut StyleCounts, seen: &mut HashSet<PathBuf>) {
+        if !seen.insert(self.identity.clone()) {
+            return;
+        }
+
+        for import in &self.dependents {
+            match import.style {
+                ImportStyle::Comment => counts.comment += 1,
+                ImportStyle::Source => counts.source += 1,
+            }
+            for dep in &import.resolved {
+                dep.collect_style_counts(counts, seen);
+            }
+        }
+    }
+
+    fn collect_stats(&self, stats: &mut Stats, seen: &mut HashSet<PathBuf>) {
+        if seen.insert(self.identity.clone()) {
+            stats.files += 1;
+            stats.input_lines += self.lines().count();
+        }
+        stats.max_depth = stats.max_depth.max(self.nested);
+
+        for import in &self.dependents {
+            for dep in &import.resolved {
+                stats.imports += 1;
+                dep.collect_stats(stats, seen);
+            }
+        }
+    }
+
+    /// create a new BashFile struct
+    pub fn new(path: PathBuf) -> Self {
+        BashFile {
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// load the file from the path
+    pub fn load(mut self, config: &Args, loader: &dyn SourceLoader) -> Result<Self, Error> {
+        let identity = loader.canonicalize(&self.path)?;
+        // checked before reading when the loader knows the size, so a huge file is never read
+        if let Some(size) = loader.size(&self.path) {
+            config.check_file_size(&self.path, size)?;
+        }
+        let start = Instant::now();
+        let mut bytes = loader.load(&self.path)?;
+        self.read_time = start.elapsed();
+        config.check_file_size(&self.path, bytes.len() as u64)?;
+        self.hash = format!("{:x}", Sha256::digest(&bytes));
+        self.size = bytes.len();
+        if let Some(command) = &config.preprocess {
+            bytes = preprocess(command, &self.path, bytes)?;
+        }
+
+        let mut contents = match String::from_utf8(bytes) {
+            Ok(contents) => contents,
+            Err(err) if config.lossy => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+            Err(err) => {
+                return Err(Error::Encoding {
+                    path: self.path,
+                    offset: err.utf8_error().valid_up_to(),
+                })
+            }
+        };
+
+        // an invisible byte order mark would end up in the middle of the bundle
+        if let Some(stripped) = contents.strip_prefix('\u{feff}') {
+            contents = String::from(stripped);
+            self.warnings.push(Warning {
+                location: Location {
+                    path: self.path.clone(),
+                    line_number: 0,
+                    line: String::from(contents.lines().next().unwrap_or_default()),
+                },
+                message: String::from("removed the byte order mark at the start of the file"),
+            });
+        }
+
+        // lines are normalized to `\n`, the output line ending is applied after resolving
+        if contents.contains('\r') {
+            self.crlf = contents.contains("\r\n");
+            contents = contents.replace("\r\n", "\n").replace('\r', "");
+        }
+
+        self.trailing_newline = contents.ends_with('\n');
+        self.contents = Some(contents);
+        self.identity = identity;
+        Ok(self)
+    }
+
+    /// only keep the lines of the region, `location` is the import of the region
+    fn select_region(
+        mut self,
+        region: &str,
+        location: Location,
+        config: &Args,
+    ) -> Result<Self, Error> {
+        let contents = self.contents.as_deref().unwrap_or_default();
+        let (first_line, lines) =
+            region_lines(contents, region, config).ok_or_else(|| Error::RegionNotFound {
+                location,
+                path: self.path.clone(),
+                region: String::from(region),
+            })?;
+        let mut selected = lines.join("\n");
+        if !lines.is_empty() {
+            selected.push('\n');
+        }
+
+        self.contents = Some(selected);
+        self.region = Some(String::from(region));
+        self.first_line = first_line;
+        Ok(self)
+    }
+
+    /// the location of the import in the file, counting the lines before the region
+    fn locate(&self, import: &ImportStatement) -> Location {
+        let mut location = import.location(&self.path);
+        location.line_number += self.first_line;
+        location
+    }
+
+    /// interate over the names of the functions defined in the file
+    pub fn functions<'a>(&'a self) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+        Box::new(self.lines().filter_map(function_name))
+    }
+
+    /// comment out the top level commands of the inlined files, every run of inlined lines is scanned on its own
+    pub fn apply_strip_toplevel(mut self, config: &Args) -> Self {
+        if !config.strip_toplevel_from_imports {
+            return self;
+        }
+
+        let lines: Vec<&str> = self.lines().collect();
+        let mut stripped = Vec::with_capacity(lines.len());
+        let mut start = 0;
+        while start < lines.len() {
+            let inlined = |index| {
+                source_map::find(&self.spans, index).is_some_and(|(path, _)| path != self.path)
+            };
+            let is_inlined = inlined(start);
+            let end = (start..lines.len())
+                .find(|x| inlined(*x) != is_inlined)
+                .unwrap_or(lines.len());
+            if is_inlined {
+                let commands = toplevel::toplevel_commands(&lines[start..end]);
+                for (line, command) in lines[start..end].iter().zip(commands) {
+                    if command {
+                        let text = line.trim_start();
+                        let indent = &line[..line.len() - text.len()];
+                        stripped.push(format!("{}{} {}", indent, config.comment_prefix, text));
+                    } else {
+                        stripped.push(String::from(*line));
+                    }
+                }
+            } else {
+                stripped.extend(lines[start..end].iter().map(|x| String::from(*x)));
+            }
+            start = end;
+        }
+
+        self.contents = Some(stripped.join("\n"));
+        self
+    }
+
+    /// warn about bash only syntax in the lines of inlined files, when the root file is a `sh` script
+    pub fn apply_compat_lint(mut self, config: &Args) -> Self {
+        let is_sh = self.lines().next().is_some_and(compat::is_sh_shebang);
+        if !config.compat_lint || !is_sh {
+            return self;
+        }
+
+        let mut literal = Literal::new();
+        let mut warnings = Vec::new();
+        for (index, line) in self.lines().enumerate() {
+            if literal.is_literal(line) {
+                continue;
+            }
+            let (path, line_number) = match source_map::find(&self.spans, index) {
+                Some((path, _)) if path == self.path => continue,
+                Some(found) => found,
+                None => continue,
+            };
+            if let Some(bashism) = compat::bashism(line) {
+                warnings.push(Warning {
+                    location: Location {
+                        path: path.to_path_buf(),
+                        line_number,
+                        line: String::from(line),
+                    },
+                    message: format!("{} is not supported by `sh`", bashism),
+                });
+            }
+        }
+
+        self.warnings.extend(warnings);
+        self
+    }
+
+    /// warn about functions that are defined more than once, or fail with `--strict`
+    pub fn apply_normalize_functions(mut self, config: &Args) -> Result<Self, Error> {
+        if !config.normalize_functions {
+            return Ok(self);
+        }
+
+        let mut literal = Literal::new();
+        let mut defined: HashMap<&str, Location> = HashMap::new();
+        let mut warnings = Vec::new();
+        for (index, line) in self.lines().enumerate() {
+            if literal.is_literal(line) {
+                continue;
+            }
+            let name = match function_name(line) {
+                Some(name) => name,
+                None => continue,
+            };
+            let location = match source_map::find(&self.spans, index) {
+                Some((path, line_number)) => Location {
+                    path: path.to_path_buf(),
+                    line_number,
+                    line: String::from(line),
+                },
+                None => Location {
+                    path: self.path.clone(),
+                    line_number: index,
+                    line: String::from(line),
+                },
+            };
+            match defined.get(name) {
+                Some(first) => {
+                    let err = Error::DuplicateFunction {
+                        name: String::from(name),
+                        first: Box::new(first.clone()),
+                        location: location.clone(),
+                    };
+                    if config.strict {
+                        return Err(err);
+                    }
+                    warnings.push(Warning {
+                        location,
+                        message: err.to_string(),
+                    });
+                }
+                None => {
+                    defined.insert(name, location);
+                }
+            }
+        }
+
+        self.warnings.extend(warnings);
+        Ok(self)
+    }
+
+    /// collapse runs of blank lines into a single blank line
+    pub fn apply_squeeze_blank(mut self, config: &Args) -> Self {
+        if !config.squeeze_blank {
+            return self;
+        }
+
+        let mut heredoc = Heredoc::new();
+        let mut previous_blank = false;
+        let mut removed = Vec::new();
+        let lines: Vec<&str> = self
+            .lines()
+            .enumerate()
+            .filter(|(index, line)| {
+                let blank = !heredoc.is_body(line) && line.trim().is_empty();
+                let keep = !(blank && previous_blank);
+                previous_blank = blank;
+                if !keep {
+                    removed.push(*index);
+                }
+                keep
+            })
+            .map(|(_, line)| line)
+            .collect();
+
+        let contents = lines.join("\n");
+        for index in removed.into_iter().rev() {
+            source_map::remove_line(&mut self.spans, index);
+        }
+        self.contents = Some(contents);
+        self
+    }
+
+    /// normalize the leading whitespace of every line
+    pub fn apply_reindent(mut self, config: &Args) -> Self {
+        let indent = match config.reindent {
+            Some(indent) => indent,
+            None => return self,
+        };
+        let width = config.indent_width.max(1);
+
+        let mut heredoc = Heredoc::new();
+        let lines: Vec<String> = self
+            .lines()
+            .map(|line| {
+                if heredoc.is_body(line) {
+                    return String::from(line);
+                }
+
+                let content = line.trim_start_matches([' ', '\t']);
+                let columns: usize = line[..line.len() - content.len()]
+                    .chars()
+                    .map(|c| if c == '\t' { width } else { 1 })
+                    .sum();
+                let leading = match indent {
+                    Indent::Spaces => " ".repeat(columns),
+                    Indent::Tabs => "\t".repeat(columns / width) + &" ".repeat(columns % width),
+                };
+                leading + content
+            })
+            .collect();
+
+        self.contents = Some(lines.join("\n"));
+        self
+    }
+
+    /// append the call to the configured main function
+    pub fn apply_call_main(mut self, config: &Args) -> Self {
+        if let Some(name) = &config.call_main {
+            if self.functions().any(|x| x == name) {
+                let contents = self.contents.get_or_insert_with(String::new);
+                contents.push_str(&format!("\n{} \"$@\"", name));
+            } else {
+                // the first line of the root file left in the bundle
+                let (line_number, line) = self
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .find_map(|(index, line)| match source_map::find(&self.spans, index) {
+                        Some((path, line_number)) if path == self.path => Some((line_number, line)),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                let location = Location {
+                    path: self.path.clone(),
+                    line_number,
+                    line: String::from(line),
+                };
+                self.warnings.push(Warning {
+                    location,
+                    message: format!("function `{}` is not defined, not calling it", name),
+                });
+            }
+        }
+        self
+    }
+
+    /// wrap the top level statements of the root file in a guard, so sourcing the bundle only defines things
+    ///
+    /// lines that come from no file, like the `--call-main` call, are guarded as well
+    pub fn apply_main_guard(mut self, config: &Args) -> Self {
+        if !config.main_guard {
+            return self;
+        }
+
+        let lines: Vec<&str> = self.lines().collect();
+        let mut from_root = vec![true; lines.len()];
+        for span in self.spans.iter().filter(|x| x.source_path != self.path) {
+            for line in &mut from_root[span.output_start..span.output_end.min(lines.len())] {
+                *line = false;
+            }
+        }
+
+        let mut heredoc = Heredoc::new();
+        let mut function_indent = None;
+        let mut guarded = Vec::with_capacity(lines.len() + 2);
+        let mut inserted = Vec::new();
+        let mut pending = Vec::new();
+        let mut open = false;
+        for (index, line) in lines.iter().enumerate() {
+            let trimmed = line.trim_start();
+            let indent = line.len() - trimmed.len();
+            let kind = if !from_root[index] {
+                Guard::Outside
+            } else if heredoc.is_body(line) {
+                match function_indent {
+                    Some(_) => Guard::Outside,
+                    None => Guard::Statement,
+                }
+            } else if let Some(function) = function_indent {
+                if trimmed.starts_with('}') && indent <= function {
+                    function_indent = None;
+                }
+                Guard::Outside
+            } else if index == 0 && line.starts_with("#!") {
+                Guard::Outside
+            } else if function_name(line).is_some() {
+                if !line.trim_end().ends_with('}') {
+                    function_indent = Some(indent);
+                }
+                Guard::Outside
+            } else if trimmed.is_empty() || trimmed.starts_with('#') {
+                Guard::Neutral
+            } else {
+                Guard::Statement
+            };
+
+            match kind {
+                Guard::Neutral if open => pending.push((index, *line)),
+                Guard::Neutral => guarded.push(*line),
+                Guard::Statement => {
+                    if !open {
+                        guarded.push(MAIN_GUARD_START);
+                        inserted.push(index);
+                        open = true;
+                    }
+                    guarded.extend(pending.drain(..).map(|(_, x)| x));
+                    guarded.push(*line);
+                }
+                Guard::Outside => {
+                    if open {
+                        guarded.push(MAIN_GUARD_END);
+                        inserted.push(pending.first().map(|(x, _)| *x).unwrap_or(index));
+                        open = false;
+                    }
+                    guarded.extend(pending.drain(..).map(|(_, x)| x));
+                    guarded.push(*line);
+                }
+            }
+        }
+        if open {
+            guarded.push(MAIN_GUARD_END);
+            inserted.push(pending.first().map(|(x, _)| *x).unwrap_or(lines.len()));
+            guarded.extend(pending.drain(..).map(|(_, x)| x));
+        }
+
+        let contents = guarded.join("\n");
+        for at in inserted.into_iter().rev() {
+            source_map::insert_lines(&mut self.spans, at, 1);
+        }
+        self.contents = Some(contents);
+        self
+    }
+
+    /// replace or insert the configured shebang line
+    pub fn apply_shebang(mut self, config: &Args) -> Self {
+        let shebang = match &config.shebang {
+            Some(shebang) => shebang,
+            None => return self,
+        };
+
+        let contents = self.contents.get_or_insert_with(String::new);
+        if contents.starts_with("#!") {
+            let end = contents.find('\n').unwrap_or(contents.len());
+            contents.replace_range(..end, shebang);
+        } else {
+            contents.insert_str(0, &format!("{}\n", shebang));
+            source_map::insert_lines(&mut self.spans, 0, 1);
+        }
+        self
+    }
+
+    /// warn about a shebang line whose interpreter is not installed, or fail with `--strict`
+    pub fn apply_interpreter_check(mut self, config: &Args) -> Result<Self, Error> {
+        if !config.interpreter_check {
+            return Ok(self);
+        }
+        let shebang = self
+            .lines()
+            .next()
+            .filter(|x| x.starts_with("#!"))
+            .map(String::from);
+        let shebang = match shebang {
+            Some(shebang) => shebang,
+            None => return Ok(self),
+        };
+        let location = match source_map::find(&self.spans, 0) {
+            Some((path, line_number)) => Location {
+                path: path.to_path_buf(),
+                line_number,
+                line: shebang.clone(),
+            },
+            None => Location {
+                path: self.path.clone(),
+                line_number: 0,
+                line: shebang.clone(),
+            },
+        };
+
+        let mut warnings = Vec::new();
+        for program in shebang_programs(&shebang) {
+            if is_installed(program) {
+                continue;
+            }
+            let err = Error::MissingInterpreter {
+                location: location.clone(),
+                interpreter: String::from(program),
+            };
+            if config.strict {
+                return Err(err);
+            }
+            warnings.push(Warning {
+                location: location.clone(),
+                message: err.to_string(),
+            });
+        }
+
+        self.warnings.extend(warnings);
+        Ok(self)
+    }
+
+    /// remove the shebang line if configured
+    pub fn apply_no_shebang(mut self, config: &Args) -> Self {
+        if config.no_shebang {
+            if let Some(contents) = self.contents.as_mut() {
+                if contents.starts_with("#!") {
+                    let end = contents.find('\n').map(|x| x + 1).unwrap_or(contents.len());
+                    contents.replace_range(..end, "");
+                    source_map::remove_line(&mut self.spans, 0);
+                }
+            }
+        }
+        self
+    }
+
+    /// insert the `--prepend` file after the shebang line and the `--append` file at the end
+    pub fn apply_injected(mut self, config: &Args) -> Result<Self, Error> {
+        if let Some(path) = &config.prepend {
+            let at = if self.lines().next().is_some_and(|x| x.starts_with("#!")) {
+                1
+            } else {
+                0
+            };
+            self.inject(path, at, config)?;
+        }
+        if let Some(path) = &config.append {
+            let at = self.lines().count();
+            self.inject(path, at, config)?;
+        }
+        Ok(self)
+    }
+
+    /// insert the contents of the file before output line `at`
+    fn inject(&mut self, path: &Path, at: usize, config: &Args) -> Result<(), Error> {
+        let mut file = BashFile::new(path.to_path_buf()).load(config, &DiskLoader)?;
+        if config.resolve_injected {
+            file = file.load_dependents(config, &DiskLoader)?;
+        }
+        let mut file = file.resolve_dependents(config)?;
+        self.warnings.append(&mut file.warnings);
+
+        let mut lines: Vec<&str> = self.lines().collect();
+        let injected: Vec<&str> = file.lines().collect();
+        lines.splice(at..at, injected.iter().copied());
+        let contents = lines.join("\n");
+
+        source_map::insert_lines(&mut self.spans, at, injected.len());
+        self.spans
+            .extend(file.spans.into_iter().map(|x| x.offset(at)));
+        self.spans.sort_by_key(|x| x.output_start);
+        self.contents = Some(contents);
+        Ok(())
+    }
+
+    /// insert the banner after the shebang line
+    pub fn apply_banner(mut self, config: &Args) -> Result<Self, Error> {
+        let banner = match (&config.banner, &config.banner_file) {
+            (Some(text), _) => text.clone(),
+            (None, Some(path)) => {
+                std::fs::read_to_string(path).map_err(|source| Error::IoWithPath {
+                    path: path.clone(),
+                    source,
+                })?
+            }
+            (None, None) => return Ok(self),
+        };
+
+        let mut banner: String = banner
+            .lines()
+            .map(|line| match line {
+                x if x.starts_with('#') => format!("{}\n", x),
+                "" => String::from("#\n"),
+                x => format!("# {}\n", x),
+            })
+            .collect();
+
+        let contents = self.contents.get_or_insert_with(String::new);
+        let position = if contents.starts_with("#!") {
+            match contents.find('\n') {
+                Some(x) => x + 1,
+                None => {
+                    contents.push('\n');
+                    contents.len()
+                }
+            }
+        } else {
+            0
+        };
+        if position == contents.len() {
+            banner.pop();
+        }
+        contents.insert_str(position, &banner);
+        let at = if position == 0 { 0 } else { 1 };
+        source_map::insert_lines(&mut self.spans, at, banner.lines().count());
+
+        Ok(self)
+    }
+
+    /// add back the trailing newline of the loaded file, which is lost while inlining
+    pub fn apply_trailing_newline(mut self, config: &Args) -> Self {
+        if self.trailing_newline && !config.quiet {
+            if let Some(contents) = self.contents.as_mut() {
+                contents.push('\n');
+            }
+        }
+        self
+    }
+
+    /// convert the `\n` line endings to the configured line ending
+    pub fn apply_line_ending(mut self, config: &Args) -> Self {
+        let crlf = match config.line_ending {
+            LineEnding::Lf => false,
+            LineEnding::Crlf => true,
+            LineEnding::Preserve => self.crlf,
+        };
+
+        if crlf {
+            self.contents = self.contents.map(|x| x.replace('\n', "\r\n"));
+        }
+        self
+    }
+
+    /// interate over the lines in the file
+    pub fn lines<'a>(&'a self) -> Box<dyn Iterator<Item = &'a str> + 'a> {
+        match self.contents {
+            None => Box::new(std::iter::empty()),
+            Some(ref input) => Box::new(input.lines()),
+        }
+    }
+
+    /// interate over the imports found in the file
+    pub fn imports<'a>(
+        &'a self,
+        config: &'a Args,
+        loader: &'a dyn SourceLoader,
+    ) -> Box<dyn Iterator<Item = Result<ImportStatement, Error>> + 'a> {
+        let path = parent_dir(&self.path);
+        let lines: Vec<&str> = self.lines().collect();
+        let mut literal = Literal::new();
+        let mut index = 0;
+        Box::new(std::iter::from_fn(move || {
+            while let Some(line) = lines.get(index) {
+                let line_number = index;
+                index += 1;
+                // an import line kept by `--keep-directives` is followed by its begin marker
+                if lines.get(index).is_some_and(|x| is_begin_marker(x, config))
+                    || literal.is_literal(line)
+                {
+                    continue;
+                }
+
+                let (line, continued) = join_continuation(&lines[line_number..], config);
+                let import = Self::to_import(&line, line_number, &path, config, loader);
+                if let Some(import) = import.transpose() {
+                    // the continuation lines are only skipped when they belong to an import
+                    index += continued;
+                    return Some(import.map(|mut x| {
+                        x.continued = continued;
+                        x
+                    }));
+                }
+            }
+            None
+        }))
+    }
+
+    /// load the imports found in the file
+    ///
+    /// with `--keep-going` the recoverable errors are collected and returned together after loading the whole tree
+    pub fn load_dependents(self, config: &Args, loader: &dyn SourceLoader) -> Result<Self, Error> {
+        let mut state = LoadState {
+            loaded: HashSet::new(),
+            errors: Vec::new(),
+            ignore: IgnoreFile::load(&parent_dir(&self.path))?,
+        };
+        let file = self.load_dependents_memoized(config, loader, &mut state)?;
+        let mut errors = state.errors;
+        match errors.len() {
+            0 => Ok(file),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::Multiple(errors)),
+        }
+    }
+
+    fn load_dependents_memoized(
+        mut self,
+        config: &Args,
+        loader: &dyn SourceLoader,
+        state: &mut LoadState,
+    ) -> Result<Self, Error> {
+        if matches!(config.max_depth(), Some(depth) if self.nested >= depth) {
+            return Ok(self);
+        }
+
+        let mut deps = Vec::new();
+
+        let mut warnings = Vec::new();
+        for import in self.imports(config, loader) {
+            let mut import = match import {
+                Ok(import) => import,
+                Err(err) => {
+                    keep_going(err, config, &mut state.errors)?;
+                    continue;
+                }
+            };
+            if config.ignore_case && import.unresolved == Some(Unresolved::NotFound) {
+                if let Some(found) = case_insensitive_match(&import.path)? {
+                    warnings.push(Warning {
+                        location: self.locate(&import),
+                        message: format!(
+                            "import {} only matches {} ignoring case",
+                            import.path.display(),
+                            found.display()
+                        ),
+                    });
+                    let (path, unresolved) = match Self::check_bash_file(found, loader) {
+                        Ok(path) => (path, None),
+                        Err((path, unresolved)) => (path, Some(unresolved)),
+                    };
+                    import.path = path;
+                    import.unresolved = unresolved;
+                }
+            }
+            let globbed = if import.unresolved.is_none() && is_glob(&import.text) {
+                let files = match glob_files(&import.path, &state.ignore, loader) {
+                    Ok(files) => files,
+                    Err(err) => {
+                        keep_going(err, config, &mut state.errors)?;
+                        continue;
+                    }
+                };
+                if files.is_empty() {
+                    import.unresolved = Some(Unresolved::NoMatch);
+                }
+                Some(files)
+            } else {
+                None
+            };
+            if let Some(unresolved) = import.unresolved {
+                if config.strict {
+                    let err = Error::Unresolved {
+                        location: self.locate(&import),
+                        path: import.path.clone(),
+                        reason: unresolved,
+                    };
+                    keep_going(err, config, &mut state.errors)?;
+                } else {
+                    warnings.push(Warning {
+                        location: self.locate(&import),
+                        message: format!(
+                            "unresolved import {}: {}",
+                            import.path.display(),
+                            unresolved
+                        ),
+                    });
+                }
+                deps.push(import);
+                continue;
+            }
+            config.log(
+                2,
+                format_args!(
+                    "import {} ({}) at {}:{}",
+                    import.text,
+                    import.style,
+                    self.path.display(),
+                    import.line_number + 1
+                ),
+            );
+            let paths = if let Some(files) = globbed {
+                files
+            } else if loader.is_dir(&import.path) {
+                directory_files(&import.path, config.recursive_dir, &state.ignore, loader)?
+            } else {
+                vec![import.path.clone()]
+            };
+
+            for path in paths {
+                let file = match self.load_dependent(&import, path, config, loader, state) {
+                    Ok(file) => file,
+                    Err(err) => {
+                        keep_going(err, config, &mut state.errors)?;
+                        continue;
+                    }
+                };
+                let checks = [
+                    self.check_root_dir(&import, &file, config, loader),
+                    self.check_not_empty(&import, &file),
+                ];
+                for checked in checks {
+                    match checked {
+                        Err(
+                            ref err @ (Error::OutsideRoot { ref location, .. }
+                            | Error::EmptyImport { ref location, .. }),
+                        ) if !config.strict => warnings.push(Warning {
+                            location: location.clone(),
+                            message: err.to_string(),
+                        }),
+                        Err(err) => keep_going(err, config, &mut state.errors)?,
+                        Ok(()) => (),
+                    }
+                }
+                import.resolved.push(file);
+            }
+            deps.push(import)
+        }
+
+        self.dependents = deps;
+        self.warnings.extend(warnings);
+        Ok(self)
+    }
+
+    /// fails if the imported file is empty or only contains whitespace, it probably was never written
+    fn check_not_empty(&self, import: &ImportStatement, file: &BashFile) -> Result<(), Error> {
+        match &file.contents {
+            Some(contents) if contents.trim().is_empty() => Err(Error::EmptyImport {
+                location: self.locate(import),
+                path: file.path.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// fails if the imported file is not inside `--root-dir`, comparing the canonical paths
+    fn check_root_dir(
+        &self,
+        import: &ImportStatement,
+        file: &BashFile,
+        config: &Args,
+        loader: &dyn SourceLoader,
+    ) -> Result<(), Error> {
+        let root = match &config.root_dir {
+            Some(root) => root,
+            None => return Ok(()),
+        };
+        let canonical_root = loader
+            .canonicalize(root)
+            .unwrap_or_else(|_| root.to_path_buf());
+        if file.identity.starts_with(&canonical_root) {
+            return Ok(());
+        }
+
+        Err(Error::OutsideRoot {
+            location: self.locate(import),
+            path: file.path.clone(),
+            root: root.to_path_buf(),
+        })
+    }
+
+    fn load_dependent(
+        &self,
+        import: &ImportStatement,
+        path: PathBuf,
+        config: &Args,
+        loader: &dyn SourceLoader,
+        state: &mut LoadState,
+    ) -> Result<BashFile, Error> {
+        config.log(1, format_args!("load {}", path.display()));
+        config.check_readable(&path, loader)?;
+        let identity = loader.canonicalize(&path)?;
+        if identity == self.identity {
+            return Err(Error::SelfImport(self.locate(import)));
+        }
+        if let Some(start) = self.ancestors.iter().position(|x| *x == identity) {
+            let mut cycle = self.ancestors[start..].to_vec();
+            cycle.push(self.identity.clone());
+            cycle.push(identity);
+            return Err(Error::Circular {
+                location: Some(self.locate(import)),
+                cycle,
+            });
+        }
+
+        let nested = self.nested + 1;
+        let key = memo_key(&identity, import.region(), nested, config);
+        if state.loaded.contains(&key) {
+            config.log(2, format_args!("reuse {}", path.display()));
+            return Ok(BashFile {
+                path,
+                identity,
+                nested,
+                memoized: true,
+                region: import.region.clone(),
+                ..Default::default()
+            });
+        }
+
+        let mut file = BashFile::new(path).load(config, loader)?;
+        if let Some(region) = &import.region {
+            file = file.select_region(region, self.locate(import), config)?;
+        }
+        file.ancestors = self.ancestors.clone();
+        file.ancestors.push(self.identity.clone());
+
+        let file = file
+            .inner_load_dependents(nested, config, loader, state)
+            .map_err(|err| match err {
+                Error::Circular {
+                    location: None,
+                    cycle,
+                } => Error::Circular {
+                    location: Some(self.locate(import)),
+                    cycle,
+                },
+                err => err,
+            })?;
+        state.loaded.insert(key);
+        Ok(file)
+    }
+
+    fn inner_load_dependents(
+        mut self,
+        nested: usize,
+        config: &Args,
+        loader: &dyn SourceLoader,
+        state: &mut LoadState,
+    ) -> Result<Self, Error> {
+        if nested > CIRCULAR_CUT_OFF {
+            return Err(Error::Circular {
+                location: None,
+                cycle: Vec::new(),
+            });
+        }
+        self.nested = nested;
+
+        self.load_dependents_memoized(config, loader, state)
+    }
+
+    /// concatenate every file of the loaded tree once, the imported files before the files importing them
+    /// and the root file last, without the resolved import lines
+    ///
+    /// `--root-order` puts the root file before the libraries, or around them at a `# (libraries)` line
+    pub fn flatten_dependents(mut self, config: &Args) -> Self {
+        let mut lines = Vec::new();
+        let mut spans = Vec::new();
+        let mut warnings = Vec::new();
+
+        // the shebang of the root file stays the first line
+        let shebang = self.lines().next().filter(|x| x.starts_with("#!"));
+        if let Some(shebang) = shebang {
+            source_map::push_line(&mut spans, 0, &self.path, 0);
+            lines.push(shebang);
+        }
+
+        let mut order = Vec::new();
+        self.collect_topological(&mut order, &mut HashSet::new());
+        // the root file is the last file in post order
+        let libraries = &order[..order.len() - 1];
+
+        let mut root_skipped = self.flattened_lines();
+        if shebang.is_some() {
+            root_skipped.insert(0);
+        }
+        let root_length = self.lines().count();
+        let split = match config.root_order {
+            RootOrder::Last => 0,
+            RootOrder::First => root_length,
+            RootOrder::Marker => {
+                let marker = format!("{}{}", config.comment_prefix, LIBRARIES_MARKER);
+                match self.lines().position(|x| x.trim() == marker) {
+                    Some(index) => {
+                        root_skipped.insert(index);
+                        index
+                    }
+                    None => {
+                        warnings.push(Warning {
+                            location: Location {
+                                path: self.path.clone(),
+                                line_number: 0,
+                                line: String::from(self.lines().next().unwrap_or_default()),
+                            },
+                            message: format!(
+                                "no `{}` line in the root file, it is put after the libraries",
+                                marker
+                            ),
+                        });
+                        0
+                    }
+                }
+            }
+        };
+
+        let mut segments = vec![(&self, 0..split, &root_skipped)];
+        let library_skipped: Vec<HashSet<usize>> =
+            libraries.iter().map(|x| x.flattened_lines()).collect();
+        for (file, skipped) in libraries.iter().zip(&library_skipped) {
+            segments.push((file, 0..usize::MAX, skipped));
+        }
+        segments.push((&self, split..root_length, &root_skipped));
+
+        for (file, range, skipped) in segments {
+            let selected = file
+                .lines()
+                .enumerate()
+                .skip(range.start)
+                .take(range.end - range.start);
+            for (index, line) in selected {
+                if !skipped.contains(&index) {
+                    source_map::push_line(
+                        &mut spans,
+                        lines.len(),
+                        &file.path,
+                        file.first_line + index,
+                    );
+                    lines.push(line);
+                }
+            }
+        }
+        for file in order {
+            warnings.extend(file.warnings.iter().cloned());
+        }
+
+        self.contents = Some(lines.join("\n"));
+        self.spans = spans;
+        self.warnings = warnings;
+        self.dependents = Vec::new();
+        self
+    }
+
+    /// the lines of the resolved imports, left out when flattening
+    fn flattened_lines(&self) -> HashSet<usize> {
+        self.dependents
+            .iter()
+            .filter(|x| x.unresolved.is_none())
+            .flat_map(|x| x.line_number..=x.line_number + x.continued)
+            .collect()
+    }
+
+    /// the files of the loaded tree in depth first post order, every file once
+    fn collect_topological<'a>(
+        &'a self,
+        order: &mut Vec<&'a BashFile>,
+        seen: &mut HashSet<(&'a Path, Option<&'a str>)>,
+    ) {
+        if !seen.insert((&self.identity, self.region.as_deref())) {
+            return;
+        }
+        for dep in self.dependents.iter().flat_map(|x| &x.resolved) {
+            dep.collect_topological(order, seen);
+        }
+        order.push(self);
+    }
+
+    /// replace the imports found in the file with the importered files
+    pub fn resolve_dependents(self, config: &Args) -> Result<Self, Error> {
+        self.resolve_dependents_memoized(config, &mut HashMap::new())
+    }
+
+    /// `resolved` contains the resolved contents of the files that are imported more than once
+    fn resolve_dependents_memoized(
+        mut self,
+        config: &Args,
+        resolved: &mut HashMap<MemoKey, (String, Vec<SourceSpan>)>,
+    ) -> Result<Self, Error> {
+        let mut size: usize = self.lines().map(|x| x.len() + 1).sum();
+        let mut inlined = HashMap::new();
+        let mut inlined_spans = HashMap::new();
+        // the separator only goes between the files inlined into the root file
+        let separator =
+            Some(config.separator.as_str()).filter(|x| self.nested == 0 && !x.is_empty());
+        let mut separated_files = 0;
+        let mut continuation_lines = HashSet::new();
+        for import in std::mem::take(&mut self.dependents) {
+            if import.unresolved.is_none() {
+                continuation_lines
+                    .extend(import.line_number + 1..=import.line_number + import.continued);
+                size -= import.line.len();
+                let mut contents = Vec::new();
+                let mut spans = Vec::new();
+                let mut offset = 0;
+                let mut inlined_files = 0;
+                if let Some(separator) = separator.filter(|_| separated_files > 0) {
+                    contents.push(String::from(separator));
+                    offset += separator.split('\n').count();
+                }
+                let mut end_marker = None;
+                if config.keep_directives {
+                    let (line, begin, end) = directive(&import, config);
+                    contents.push(line);
+                    contents.push(begin);
+                    spans.push(SourceSpan {
+                        output_start: 0,
+                        output_end: 1,
+                        source_path: self.path.clone(),
+                        source_start: self.first_line + import.line_number,
+                    });
+                    offset += 2;
+                    end_marker = Some(end);
+                } else if config.keep_source_ref {
+                    contents.push(source_ref(&import, config));
+                    spans.push(SourceSpan {
+                        output_start: 0,
+                        output_end: 1,
+                        source_path: self.path.clone(),
+                        source_start: self.first_line + import.line_number,
+                    });
+                    offset += 1;
+                }
+                if config.keep_import_markers {
+                    contents.push(format!(
+                        "{} (inlined {})",
+                        config.comment_prefix,
+                        marker_text(&import, config)
+                    ));
+                    offset += 1;
+                }
+                for dep in import.resolved {
+                    config.log(
+                        1,
+                        format_args!(
+                            "inline {} into {}:{}",
+                            dep.path.display(),
+                            self.path.display(),
+                            import.line_number + 1
+                        ),
+                    );
+                    let key = memo_key(&dep.identity, dep.region.as_deref(), dep.nested, config);
+                    let (mut dep_contents, mut dep_spans) = if dep.memoized {
+                        resolved
+                            .get(&key)
+                            .cloned()
+                            .expect("files are resolved before they are reused")
+                    } else {
+                        let mut loaded_dep = dep.resolve_dependents_memoized(config, resolved)?;
+                        self.warnings.append(&mut loaded_dep.warnings);
+                        let entry = (loaded_dep.contents.unwrap_or_default(), loaded_dep.spans);
+                        resolved.insert(key, entry.clone());
+                        entry
+                    };
+                    if config.blank_between_files || config.trim_imported {
+                        dep_contents = trim_blank_lines(&dep_contents, &mut dep_spans);
+                        if dep_contents.is_empty() {
+                            continue;
+                        }
+                    }
+                    if config.blank_between_files && inlined_files > 0 {
+                        contents.push(String::new());
+                        offset += 1;
+                    }
+                    if let Some(separator) = separator.filter(|_| inlined_files > 0) {
+                        contents.push(String::from(separator));
+                        offset += separator.split('\n').count();
+                    }
+                    inlined_files += 1;
+                    spans.extend(dep_spans.into_iter().map(|x| x.offset(offset)));
+                    offset += dep_contents.split('\n').count();
+                    size += dep_contents.len() + 1;
+                    config.check_output_size(size)?;
+                    contents.push(dep_contents);
+                }
+                if let Some(end) = end_marker {
+                    contents.push(end);
+                    offset += 1;
+                }
+                if let Some(trailing) = &import.trailing {
+                    spans.push(SourceSpan {
+                        output_start: offset,
+                        output_end: offset + 1,
+                        source_path: self.path.clone(),
+                        source_start: self.first_line + import.line_number,
+                    });
+                    size += trailing.len() + 1;
+                    config.check_output_size(size)?;
+                    contents.push(trailing.clone());
+                }
+                separated_files += inlined_files;
+                inlined_spans.insert(import.line_number, spans);
+                inlined.insert(import.line_number, contents.join("\n"));
+            }
+        }
+
+        // a single pass over the lines, substituting the inlined contents at the import lines
+        let mut entries: Vec<(Option<usize>, String)> = self
+            .lines()
+            .enumerate()
+            .filter(|(index, _)| !continuation_lines.contains(index))
+            .map(|(index, line)| {
+                let line = inlined.remove(&index).unwrap_or_else(|| String::from(line));
+                (Some(index), line)
+            })
+            .collect();
+        if config.blank_between_files {
+            entries = separate_inlined(entries, &inlined_spans);
+        }
+
+        let mut spans = Vec::new();
+        let mut output_line = 0;
+        for (index, line) in &entries {
+            match index.and_then(|x| inlined_spans.remove(&x)) {
+                Some(dep_spans) => {
+                    spans.extend(dep_spans.into_iter().map(|x| x.offset(output_line)))
+                }
+                None => {
+                    if let Some(index) = index {
+                        source_map::push_line(
+                            &mut spans,
+                            output_line,
+                            &self.path,
+                            self.first_line + index,
+                        )
+                    }
+                }
+            }
+            output_line += line.split('\n').count();
+        }
+
+        let lines: Vec<String> = entries.into_iter().map(|(_, line)| line).collect();
+        self.contents = Some(lines.join("\n"));
+        self.spans = spans;
+        Ok(self)
+    }
+
+    fn to_import(
+        input: &str,
+        line_number: usize,
+        path: &Path,
+        config: &Args,
+        loader: &dyn SourceLoader,
+    ) -> Result<Option<ImportStatement>, Error> {
+        // is comment style
+        let comment = input
+            .strip_prefix(config.comment_prefix.as_str())
+            .and_then(strip_marked_import_keyword)
+            .filter(|(marker, _)| match marker {
+                ImportMarker::Plain => config.replace_comment || config.auto,
+                ImportMarker::Forced => true,
+                ImportMarker::Optional => config.optional_imports,
+            });
+        if let Some((_, x)) = comment {
+            if x.ends_with(SOURCE_REF_SUFFIX) {
+                return Ok(None);
+            }
+            let x = match x.split_once(" if ") {
+                Some((x, condition)) => {
+                    if !import_condition(condition)? {
+                        return Ok(None);
+                    }
+                    x
+                }
+                None => x,
+            };
+            let x = unquote(x);
+            let (file, region) = split_region(x);
+            let expanded = config.normalize_separators(config.expand_env(file)?);
+            let checked = match config.expand_alias(&expanded)? {
+                // an alias is not relative to the current file and not retried in the search path
+                Some(aliased) => {
+                    Self::to_valid_bash_file(Path::new(""), &aliased.to_string_lossy(), &[], loader)
+                }
+                None => {
+                    let base_path = config.import_base.as_deref().unwrap_or(path);
+                    Self::to_valid_bash_file(base_path, &expanded, &config.search_path, loader)
+                }
+            };
+            let statement =
+                Self::to_statement(input, x, line_number, ImportStyle::Comment, checked, config);
+            return Ok(statement.map(|mut statement| {
+                statement.region = region.map(String::from);
+                statement
+            }));
+        }
+
+        if config.replace_source || config.auto {
+            if let Some(x) = strip_source_prefix(input) {
+                let (x, rest) = split_source_path(x);
+                let base_path = if config.source_relative_to_file {
+                    Cow::Borrowed(path)
+                } else {
+                    // without a root file there is nothing to resolve the path relative to
+                    Cow::Owned(parent_dir(
+                        config.root_path.as_ref().ok_or(Error::MissingRoot)?,
+                    ))
+                };
+                let checked = match config.expand_env(x) {
+                    Ok(expanded) => {
+                        let expanded = config.normalize_separators(expanded);
+                        match config.expand_alias(&expanded)? {
+                            Some(aliased) => Self::to_valid_bash_file(
+                                Path::new(""),
+                                &aliased.to_string_lossy(),
+                                &[],
+                                loader,
+                            ),
+                            None => {
+                                if config.auto && !config.source_relative_to_file {
+                                    Self::check_unambiguous_source(
+                                        x, &base_path, path, &expanded, loader,
+                                    )?;
+                                }
+                                Self::to_valid_bash_file(
+                                    &base_path,
+                                    &expanded,
+                                    &config.search_path,
+                                    loader,
+                                )
+                            }
+                        }
+                    }
+                    // a `source` line is valid bash, the variable can be set when the script runs
+                    Err(Error::UnsetVariable(_)) => {
+                        Err((PathBuf::from(x), Unresolved::UnsetVariable))
+                    }
+                    Err(err) => return Err(err),
+                };
+                let statement =
+                    Self::to_statement(input, x, line_number, ImportStyle::Source, checked, config);
+                return Ok(statement.map(|mut x| {
+                    x.trailing = trailing_command(rest);
+                    x
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// fails if the `source` path matches different files relative to the root file and the current file
+    fn check_unambiguous_source(
+        text: &str,
+        root_base: &Path,
+        file_base: &Path,
+        expanded: &str,
+        loader: &dyn SourceLoader,
+    ) -> Result<(), Error> {
+        if root_base == file_base || is_glob(expanded) {
+            return Ok(());
+        }
+        let from_root = Self::to_valid_bash_file(root_base, expanded, &[], loader);
+        let from_file = Self::to_valid_bash_file(file_base, expanded, &[], loader);
+        if let (Ok(from_root), Ok(from_file)) = (from_root, from_file) {
+            if loader.canonicalize(&from_root)? != loader.canonicalize(&from_file)? {
+                return Err(Error::AmbiguousSource {
+                    text: String::from(text),
+                    matches: vec![from_root, from_file],
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn to_statement(
+        input: &str,
+        text: &str,
+        line_number: usize,
+        style: ImportStyle,
+        checked: Result<PathBuf, (PathBuf, Unresolved)>,
+        config: &Args,
+    ) -> Option<ImportStatement> {
+        let (path, unresolved) = match checked {
+            Ok(path) => (path, None),
+            Err((path, unresolved)) => (path, Some(unresolved)),
+        };
+        if config.is_excluded(text, &path) {
+            return None;
+        }
+
+        Some(ImportStatement {
+            line: String::from(input),
+            path,
+            text: String::from(text),
+            style,
+            resolved: Vec::new(),
+            unresolved,
+            trailing: None,
+            continued: 0,
+            region: None,
+            line_number,
+        })
+    }
+
+    /// a relative path that does not resolve relative to `path` is retried relative to the `search_path` directories
+    fn to_valid_bash_file(
+        base_path: &Path,
+        to_test_file: &str,
+        search_path: &[PathBuf],
+        loader: &dyn SourceLoader,
+    ) -> Result<PathBuf, (PathBuf, Unresolved)> {
+        let import_path = Path::new(to_test_file);
+        // the files matching a glob are found when the imports are loaded
+        if is_glob(to_test_file) {
+            return Ok(base_path.join(import_path));
+        }
+        if !import_path.is_relative() {
+            return Self::check_bash_file(PathBuf::from(import_path), loader);
+        }
+
+        let checked = Self::check_bash_file(base_path.join(import_path), loader);
+        if checked.is_ok() {
+            return checked;
+        }
+        search_path
+            .iter()
+            .find_map(|dir| Self::check_bash_file(dir.join(import_path), loader).ok())
+            .map_or(checked, Ok)
+    }
+
+    /// check that the path exists and is a directory or has a shell file extension
+    fn check_bash_file(
+        path: PathBuf,
+        loader: &dyn SourceLoader,
+    ) -> Result<PathBuf, (PathBuf, Unresolved)> {
+        if !loader.exists(&path) {
+            return Err((path, Unresolved::NotFound));
+        }
+        if loader.is_dir(&path) {
+            return Ok(path);
+        }
+
+        match path.extension() {
+            Some(ext) if ALLOWED_EXTENSIONS.contains(&ext.to_str()) => Ok(path),
+            _ => Err((path, Unresolved::Extension)),
+        }
+    }
+}
+
+#[test]
+fn resolving_one_level() {
+    let file = BashFile::resolve("./tests/one.sh".into(), &Args::default()).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_two_level() {
+    let file = BashFile::resolve("./tests/two.sh".into(), &Args::default()).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+
+super_yell() {
+    yell "$1 !!!!!!"
+}
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+super_yell "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_trim_imported() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./padded.sh\n# import ./empty.sh\necho main\n"),
+    );
+    files.insert(
+        PathBuf::from("padded.sh"),
+        String::from("\n\necho padded\n\necho again\n\n"),
+    );
+    files.insert(PathBuf::from("empty.sh"), String::from("\n\n"));
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!(
+        "\n\necho padded\n\necho again\n\n\n\necho main\n",
+        file.to_string()
+    );
+
+    let args = Args {
+        trim_imported: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!("echo padded\n\necho again\n\necho main\n", file.to_string());
+    assert_eq!(
+        Some((Path::new("././padded.sh"), 4)),
+        source_map::find(file.spans(), 2)
+    );
+}
+
+#[test]
+fn resolving_crlf() {
+    let file = BashFile::resolve("./tests/crlf.sh".into(), &Args::default()).unwrap();
+
+    let expected =
+        "yell() {\n    echo \"$1 !!!\" | tr '[:lower:]' '[:upper:]'\n}\nyell \"hallo\"\n";
+
+    assert_eq!(expected, file.to_string());
+
+    let args = Args {
+        line_ending: LineEnding::Preserve,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/crlf.sh".into(), &args).unwrap();
+
+    assert_eq!(expected.replace('\n', "\r\n"), file.to_string())
+}
+
+#[test]
+fn resolving_call_main() {
+    let args = Args {
+        call_main: Some(String::from("main")),
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/main.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+main() {
+    yell "$1"
+}
+main "$@"
+"#;
+
+    assert_eq!(expected, file.to_string());
+
+    let args = Args {
+        call_main: Some(String::from("not_defined")),
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/main.sh".into(), &args).unwrap();
+
+    assert!(!file.to_string().contains("not_defined"));
+    assert_eq!(1, file.warnings.len());
+    assert_eq!(
+        "function `not_defined` is not defined, not calling it",
+        file.warnings[0].message
+    );
+}
+
+#[test]
+fn function_names() {
+    assert_eq!(Some("yell"), function_name("yell() {"));
+    assert_eq!(Some("yell"), function_name("  yell () {"));
+    assert_eq!(Some("yell"), function_name("function yell {"));
+    assert_eq!(Some("yell"), function_name("function yell() {"));
+    assert_eq!(None, function_name("yell \"hallo\""));
+    assert_eq!(None, function_name("echo $(pwd)"));
+    assert_eq!(None, function_name("x=(1 2 3)"));
+}
+
+#[test]
+fn loading_missing_file() {
+    let err = BashFile::new("./tests/missing.sh".into())
+        .load(&Args::default(), &DiskLoader)
+        .unwrap_err()
+        .to_string();
+
+    assert!(err.starts_with("failed to read ./tests/missing.sh: "));
+}
+
+#[test]
+fn two_level_stats() {
+    let file = BashFile::new("./tests/two.sh".into())
+        .load(&Args::default(), &DiskLoader)
+        .unwrap()
+        .load_dependents(&Args::default(), &DiskLoader)
+        .unwrap();
+
+    let expected = Stats {
+        files: 5,
+        imports: 4,
+        input_lines: 17,
+        output_lines: 0,
+        max_depth: 2,
+    };
+
+    assert_eq!(expected, file.stats())
+}
+
+#[test]
+fn iterating_all_imports() {
+    let file = BashFile::new("./tests/two.sh".into())
+        .load(&Args::default(), &DiskLoader)
+        .unwrap()
+        .load_dependents(&Args::default(), &DiskLoader)
+        .unwrap();
+
+    let imports: Vec<_> = file
+        .all_imports()
+        .map(|x| (x.line_number(), x.text(), x.style()))
+        .collect();
+
+    assert_eq!(
+        vec![
+            (0, "./bash/two_utils.sh", ImportStyle::Comment),
+            (0, "./one_utils.sh", ImportStyle::Comment),
+            (1, "./two_empty.bash", ImportStyle::Comment),
+            (1, "./bash/one_more_utils.sh", ImportStyle::Comment),
+        ],
+        imports
+    );
+    assert_eq!(
+        Path::new("./tests/./bash/./one_utils.sh"),
+        file.all_imports().nth(1).unwrap().path()
+    );
+    assert!(file.all_imports().all(|x| x.unresolved().is_none()));
+}
+
+#[test]
+fn resolving_symlink_circular() {
+    let err = BashFile::resolve("./tests/symlink.sh".into(), &Args::default()).unwrap_err();
+
+    match err {
+        Error::SelfImport(location) => {
+            assert_eq!(Path::new("./tests/./bash/symlink_utils.sh"), location.path);
+            assert_eq!("# import ./symlink_utils_link.sh", location.line);
+        }
+        err => panic!("expected self import error, got: {}", err),
+    }
+}
+
+#[test]
+fn resolving_with_map() {
+    let args = Args {
+        banner: Some(String::from("generated")),
+        ..Args::default()
+    };
+    let (output, spans) = BashFile::resolve_with_map("./tests/one.sh".into(), &args).unwrap();
+
+    assert_eq!(9, output.lines().count());
+
+    let span = |output_start, output_end, source_path: &str, source_start| SourceSpan {
+        output_start,
+        output_end,
+        source_path: source_path.into(),
+        source_start,
+    };
+    let expected = vec![
+        span(1, 4, "./tests/./bash/one_utils.sh", 0),
+        span(4, 7, "./tests/./bash/one_more_utils.sh", 0),
+        span(7, 9, "./tests/one.sh", 2),
+    ];
+
+    assert_eq!(expected, spans)
+}
+
+#[test]
+fn output_line_to_source() {
+    let (_, map) = BashFile::resolve_with_map("./tests/one.sh".into(), &Args::default()).unwrap();
+
+    assert_eq!(
+        Some((PathBuf::from("./tests/./bash/one_more_utils.sh"), 1)),
+        BashFile::output_line_to_source(&map, 4)
+    );
+    assert_eq!(
+        Some((PathBuf::from("./tests/one.sh"), 3)),
+        BashFile::output_line_to_source(&map, 7)
+    );
+    assert_eq!(None, BashFile::output_line_to_source(&map, 100));
+}
+
+#[test]
+fn resolving_circular() {
+    let file = BashFile::resolve("./tests/circular.sh".into(), &Args::default())
+        .unwrap_err()
+        .to_string();
+    let expected = Error::Circular {
+        location: None,
+        cycle: Vec::new(),
+    }
+    .to_string();
+    assert_eq!(expected, file)
+}
+
+#[test]
+fn resolving_source() {
+    let mut args = Args::default();
+    args.root_path = Some("./tests/source.sh".into());
+    args.replace_source = true;
+    args.replace_comment = false;
+
+    let file = BashFile::resolve("./tests/source.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    echo "$1"
+}
+
+this_is_from_sourced_file() {
+    yell "$1 !!!!!!"
+}
+
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_quoted_source() {
+    let args = Args {
+        root_path: Some("./tests/source_quoted.sh".into()),
+        replace_source: true,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve("./tests/source_quoted.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    echo "$1"
+}
+source "./bash/one_utils.sh
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_dot_source() {
+    let args = Args {
+        root_path: Some("./tests/dot.sh".into()),
+        replace_source: true,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve("./tests/dot.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+./bash/one_more_utils.sh
+.hidden
+yell "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_source_without_root() {
+    let mut files = HashMap::new();
+    files.insert(PathBuf::from("main.sh"), String::from("source ./utils.sh"));
+    files.insert(PathBuf::from("utils.sh"), String::from("echo hallo"));
+    let args = Args {
+        replace_source: true,
+        ..Args::default()
+    };
+
+    assert!(matches!(
+        BashFile::resolve_with_loader("main.sh".into(), &args, &files),
+        Err(Error::MissingRoot)
+    ));
+}
+
+#[test]
+fn resolving_source_relative_to_file() {
+    let args = Args {
+        root_path: Some("./tests/source_relative.sh".into()),
+        replace_source: true,
+        source_relative_to_file: true,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve("./tests/source_relative.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_comment_prefix() {
+    let args = Args {
+        comment_prefix: String::from("--"),
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve("./tests/comment_prefix.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+# import ./bash/one_more_utils.sh
+yell "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_directory() {
+    let file = BashFile::resolve("./tests/directory.sh".into(), &Args::default()).unwrap();
+
+    let expected = r#"a() {
+    echo "a"
+}
+b() {
+    echo "b"
+}
+a
+"#;
+
+    assert_eq!(expected, file.to_string());
+
+    let args = Args {
+        recursive_dir: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/directory.sh".into(), &args).unwrap();
+
+    let expected = r#"a() {
+    echo "a"
+}
+b() {
+    echo "b"
+}
+c() {
+    echo "c"
+}
+a
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn resolving_skips_literals() {
+    let file = BashFile::resolve("./tests/literal.sh".into(), &Args::default()).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+cat <<EOF
+# import ./bash/one_more_utils.sh
+EOF
+msg="
+# import ./bash/one_more_utils.sh
+"
+yell "$msg"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn valid_bash_file_absolute_and_relative() {
+    let absolute = std::env::current_dir()
+        .unwrap()
+        .join("tests/bash/one_utils.sh");
+    let absolute_str = absolute.to_str().unwrap();
+
+    assert_eq!(
+        Ok(absolute.clone()),
+        BashFile::to_valid_bash_file(
+            Path::new("./tests/bash/nested"),
+            absolute_str,
+            &[],
+            &DiskLoader
+        )
+    );
+    assert_eq!(
+        Ok(PathBuf::from("./tests/./bash/one_utils.sh")),
+        BashFile::to_valid_bash_file(
+            Path::new("./tests"),
+            "./bash/one_utils.sh",
+            &[],
+            &DiskLoader
+        )
+    );
+    assert_eq!(
+        Err((PathBuf::from("/no/such/dir/x.sh"), Unresolved::NotFound)),
+        BashFile::to_valid_bash_file(Path::new("./tests"), "/no/such/dir/x.sh", &[], &DiskLoader)
+    );
+}
+
+#[test]
+fn resolving_search_path() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("src/main.sh"),
+        String::from("# import ./common.sh\n# import ./local.sh\necho main\n"),
+    );
+    files.insert(PathBuf::from("src/local.sh"), String::from("echo local"));
+    files.insert(PathBuf::from("lib/local.sh"), String::from("echo shadowed"));
+    files.insert(
+        PathBuf::from("shared/common.sh"),
+        String::from("echo first"),
+    );
+    files.insert(PathBuf::from("lib/common.sh"), String::from("echo second"));
+    let args = Args {
+        search_path: vec!["./shared".into(), "./lib".into()],
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve_with_loader("./src/main.sh".into(), &args, &files).unwrap();
+
+    assert_eq!("echo first\necho local\necho main\n", file.to_string());
+    assert!(file.warnings.is_empty());
+
+    let args = Args {
+        strict: true,
+        ..Args::default()
+    };
+    match BashFile::resolve_with_loader("./src/main.sh".into(), &args, &files) {
+        Err(Error::Unresolved { location, .. }) => assert_eq!(0, location.line_number),
+        other => panic!("expected an unresolved import error, got {:?}", other),
+    }
+}
+
+#[test]
+fn resolving_backslash_separators() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import .\\utils\\util.sh\necho main\n"),
+    );
+    files.insert(PathBuf::from("utils/util.sh"), String::from("echo util"));
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!(1, file.warnings.len());
+
+    let args = Args {
+        normalize_separators: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!("echo util\necho main\n", file.to_string());
+    assert!(file.warnings.is_empty());
+}
+
+#[test]
+fn resolving_max_file_size() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./big.sh\n"),
+    );
+    files.insert(PathBuf::from("big.sh"), "echo hallo\n".repeat(10));
+    let args = Args {
+        max_file_size: Some(50),
+        ..Args::default()
+    };
+
+    match BashFile::resolve_with_loader("main.sh".into(), &args, &files) {
+        Err(Error::FileTooLarge { path, limit }) => {
+            assert_eq!(Path::new("././big.sh"), path);
+            assert_eq!(50, limit);
+        }
+        other => panic!("expected a file too large error, got {:?}", other),
+    }
+}
+
+#[test]
+fn resolving_import_continuation() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./very/long/\\\n#   path/\\\n#   file.sh\n# not \\\n# import ./other.sh\necho main\n"),
+    );
+    files.insert(
+        PathBuf::from("very/long/path/file.sh"),
+        String::from("echo file"),
+    );
+    files.insert(PathBuf::from("other.sh"), String::from("echo other"));
+
+    let args = Args {
+        import_continuation: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!(
+        "echo file\n# not \\\necho other\necho main\n",
+        file.to_string()
+    );
+    assert!(file.warnings.is_empty());
+    assert_eq!(
+        Some((Path::new("main.sh"), 3)),
+        source_map::find(file.spans(), 1)
+    );
+
+    let args = Args {
+        import_continuation: true,
+        mode: Mode::Flatten,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!(
+        "echo file\necho other\n# not \\\necho main\n",
+        file.to_string()
+    );
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!(1, file.warnings.len());
+}
+
+#[test]
+fn resolving_compat_lint() {
+    let file = BashFile::resolve("./tests/posix.sh".into(), &Args::default()).unwrap();
+    assert!(file.warnings.is_empty());
+
+    let args = Args {
+        compat_lint: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/posix.sh".into(), &args).unwrap();
+
+    let locations: Vec<_> = file
+        .warnings
+        .iter()
+        .map(|x| (x.location.path.as_path(), x.location.line_number))
+        .collect();
+    assert_eq!(
+        vec![
+            (Path::new("./tests/./bash/bash_only.sh"), 1),
+            (Path::new("./tests/./bash/bash_only.sh"), 2),
+        ],
+        locations
+    );
+    assert_eq!(
+        "an array assignment is not supported by `sh`",
+        file.warnings[0].message
+    );
+}
+
+#[test]
+fn resolving_strip_toplevel() {
+    let expected = r#"#!/usr/bin/env bash
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+# demo
+# if [ -n "$DEMO" ]; then
+    # yell "demo"
+# fi
+# yell "hallo"
+yell "world"
+"#;
+
+    let args = Args {
+        strip_toplevel_from_imports: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/strip_toplevel.sh".into(), &args).unwrap();
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/./bash/demo_utils.sh"), 8)),
+        source_map::find(file.spans(), 9)
+    );
+
+    let args = Args {
+        strip_toplevel_from_imports: true,
+        mode: Mode::Flatten,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/strip_toplevel.sh".into(), &args).unwrap();
+    assert_eq!(expected, file.to_string());
+
+    let file = BashFile::resolve("./tests/strip_toplevel.sh".into(), &Args::default()).unwrap();
+    assert!(file.to_string().contains("\nyell \"hallo\"\n"));
+}
+
+#[test]
+fn region_paths() {
+    assert_eq!(("./lib.sh", Some("greet")), split_region("./lib.sh#greet"));
+    assert_eq!(("./lib.sh", None), split_region("./lib.sh"));
+    assert_eq!(("./lib.sh#", None), split_region("./lib.sh#"));
+    assert_eq!(("./a#b/lib.sh", None), split_region("./a#b/lib.sh"));
+    assert_eq!(("#greet", None), split_region("#greet"));
+}
+
+#[test]
+fn resolving_region() {
+    let file = BashFile::resolve("./tests/region.sh".into(), &Args::default()).unwrap();
+    let expected = r#"#!/usr/bin/env bash
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+greet() {
+    yell "hello $1"
+}
+greet "world"
+"#;
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/./bash/regions.sh"), 4)),
+        source_map::find(file.spans(), 4)
+    );
+
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./lib.sh#b\n# import ./lib.sh#a\n"),
+    );
+    files.insert(
+        PathBuf::from("lib.sh"),
+        String::from(
+            "# region a\na\n# endregion a\n# region b\n# import ./missing.sh\nb\n# endregion b\n",
+        ),
+    );
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!("# import ./missing.sh\nb\na\n", file.to_string());
+    assert_eq!(1, file.warnings.len());
+    assert_eq!(4, file.warnings[0].location.line_number);
+
+    let args = Args {
+        mode: Mode::Flatten,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!("# import ./missing.sh\nb\na\n", file.to_string());
+
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./lib.sh#c\n"),
+    );
+    match BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files) {
+        Err(Error::RegionNotFound {
+            location, region, ..
+        }) => {
+            assert_eq!("c", region);
+            assert_eq!(0, location.line_number);
+        }
+        other => panic!("expected a region not found error, got: {:?}", other),
+    }
+}
+
+#[test]
+fn shebang_interpreters() {
+    assert_eq!(vec!["/bin/bash"], shebang_programs("#!/bin/bash -e"));
+    assert_eq!(
+        vec!["/usr/bin/env", "bash"],
+        shebang_programs("#!/usr/bin/env bash")
+    );
+    assert_eq!(
+        vec!["/usr/bin/env", "bash"],
+        shebang_programs("#! /usr/bin/env -S LANG=C bash -e")
+    );
+    assert!(shebang_programs("echo hallo").is_empty());
+    assert!(shebang_programs("#!").is_empty());
+}
+
+#[test]
+fn resolving_interpreter_check() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("#!/usr/bin/env bash_bundler_missing_shell\necho hallo\n"),
+    );
+    files.insert(
+        PathBuf::from("installed.sh"),
+        String::from("#!/bin/sh\necho hallo\n"),
+    );
+
+    let args = Args {
+        interpreter_check: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("installed.sh".into(), &args, &files).unwrap();
+    assert!(file.warnings.is_empty());
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!(1, file.warnings.len());
+    assert_eq!(
+        "interpreter `bash_bundler_missing_shell` of the shebang line is not installed or not on the PATH",
+        file.warnings[0].message
+    );
+    assert_eq!(0, file.warnings[0].location.line_number);
+
+    let args = Args {
+        interpreter_check: true,
+        shebang: Some(String::from("#!/bash_bundler/missing/bash")),
+        strict: true,
+        ..Args::default()
+    };
+    match BashFile::resolve_with_loader("installed.sh".into(), &args, &files) {
+        Err(Error::MissingInterpreter { interpreter, .. }) => {
+            assert_eq!("/bash_bundler/missing/bash", interpreter)
+        }
+        other => panic!("expected a missing interpreter error, got: {:?}", other),
+    }
+}
+
+#[test]
+fn resolving_recursive_glob() {
+    let file = BashFile::resolve("./tests/plugins.sh".into(), &Args::default()).unwrap();
+    let functions: Vec<_> = file.functions().collect();
+
+    assert_eq!(vec!["plugin_a", "plugin_b", "plugin_c"], functions);
+    assert!(file.warnings.is_empty());
+}
+
+#[test]
+fn directory_files_symlink_cycle() {
+    // `tests/plugins/nested/deeper/loop` links back to `tests/plugins/nested`
+    let files = directory_files(
+        Path::new("./tests/plugins"),
+        true,
+        &IgnoreFile::default(),
+        &DiskLoader,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            PathBuf::from("./tests/plugins/a.sh"),
+            PathBuf::from("./tests/plugins/nested/b.sh"),
+            PathBuf::from("./tests/plugins/nested/deeper/c.sh"),
+        ],
+        files
+    );
+}
+
+#[test]
+fn glob_files_depth() {
+    let files = glob_files(
+        Path::new("./tests/./plugins/*.sh"),
+        &IgnoreFile::default(),
+        &DiskLoader,
+    )
+    .unwrap();
+    assert_eq!(vec![PathBuf::from("./tests/plugins/a.sh")], files);
+
+    let files = glob_files(
+        Path::new("./tests/plugins/**/*.sh"),
+        &IgnoreFile::default(),
+        &DiskLoader,
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            PathBuf::from("./tests/plugins/a.sh"),
+            PathBuf::from("./tests/plugins/nested/b.sh"),
+            PathBuf::from("./tests/plugins/nested/deeper/c.sh"),
+        ],
+        files
+    );
+
+    assert!(glob_files(
+        Path::new("./tests/plugins/**/*.bash"),
+        &IgnoreFile::default(),
+        &DiskLoader
+    )
+    .unwrap()
+    .is_empty());
+    assert!(matches!(
+        glob_files(
+            Path::new("./tests/plugins/[a.sh"),
+            &IgnoreFile::default(),
+            &DiskLoader
+        ),
+        Err(Error::Glob { .. })
+    ));
+}
+
+#[test]
+fn resolving_glob_in_memory() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./plugins/**/*.sh\necho main\n"),
+    );
+    files.insert(PathBuf::from("plugins/b.sh"), String::from("echo b\n"));
+    files.insert(PathBuf::from("plugins/a.sh"), String::from("echo a\n"));
+    files.insert(
+        PathBuf::from("plugins/nested/c.sh"),
+        String::from("echo c\n"),
+    );
+    files.insert(PathBuf::from("plugins/notes.txt"), String::from("notes\n"));
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!("echo a\necho b\necho c\necho main\n", file.to_string());
+}
+
+#[test]
+fn resolving_glob_without_matches() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./plugins/**/*.zsh\n"),
+    );
+    files.insert(PathBuf::from("plugins/a.sh"), String::from("echo a\n"));
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!("# import ./plugins/**/*.zsh\n", file.to_string());
+    assert_eq!(1, file.warnings.len());
+
+    let args = Args {
+        strict: true,
+        ..Args::default()
+    };
+    assert!(matches!(
+        BashFile::resolve_with_loader("main.sh".into(), &args, &files),
+        Err(Error::Unresolved {
+            reason: Unresolved::NoMatch,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn resolving_main_guard() {
+    let config = Args {
+        main_guard: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/guard.sh".into(), &config).unwrap();
+
+    let expected = r#"#!/usr/bin/env bash
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+greet() {
+    yell "hello $1"
+}
+
+# entry point
+if [[ "${BASH_SOURCE[0]}" == "${0}" ]]; then
+greet "$1"
+echo done
+fi
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/guard.sh"), 8)),
+        source_map::find(file.spans(), 11)
+    );
+}
+
+#[test]
+fn import_conditions() {
+    std::env::set_var("BASH_BUNDLER_CONDITION_TEST", "linux");
+
+    assert!(import_condition("$BASH_BUNDLER_CONDITION_TEST == linux").unwrap());
+    assert!(import_condition("${BASH_BUNDLER_CONDITION_TEST} != 'macos'").unwrap());
+    assert!(!import_condition("$BASH_BUNDLER_CONDITION_UNSET == linux").unwrap());
+
+    for invalid in &[
+        "BASH_BUNDLER_CONDITION_TEST == linux",
+        "$BASH_BUNDLER_CONDITION_TEST = linux",
+        "$BASH_BUNDLER_CONDITION_TEST == linux or",
+        "${BASH_BUNDLER_CONDITION_TEST == linux",
+        "$1 == linux",
+    ] {
+        assert!(matches!(
+            import_condition(invalid),
+            Err(Error::Condition(_))
+        ));
+    }
+}
+
+#[test]
+fn resolving_auto_styles() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./lib/a.sh\nsource ./lib/b.sh\n"),
+    );
+    files.insert(PathBuf::from("lib/a.sh"), String::from("source ./c.sh\n"));
+    files.insert(PathBuf::from("lib/b.sh"), String::from("echo b\n"));
+    files.insert(PathBuf::from("c.sh"), String::from("echo c\n"));
+    let args = Args {
+        root_path: Some("main.sh".into()),
+        auto: true,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!("echo c\necho b\n", file.to_string());
+
+    files.insert(PathBuf::from("lib/c.sh"), String::from("echo other c\n"));
+    assert!(matches!(
+        BashFile::resolve_with_loader("main.sh".into(), &args, &files),
+        Err(Error::AmbiguousSource { .. })
+    ));
+
+    let args = Args {
+        source_relative_to_file: true,
+        ..args
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!("echo other c\necho b\n", file.to_string());
+}
+
+#[test]
+fn resolving_adjacent_imports() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./a.sh\n# import ./b.sh\n# import ./a.sh\necho end\n"),
+    );
+    files.insert(PathBuf::from("a.sh"), String::from("echo a1\necho a2\n"));
+    files.insert(PathBuf::from("b.sh"), String::from("\necho b\n\n"));
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+
+    let expected = "echo a1\necho a2\n\necho b\n\necho a1\necho a2\necho end\n";
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("././a.sh"), 1)),
+        source_map::find(file.spans(), 6)
+    );
+    assert_eq!(
+        Some((Path::new("main.sh"), 3)),
+        source_map::find(file.spans(), 7)
+    );
+}
+
+#[test]
+fn resolving_duplicate_functions() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./a.sh\n# import ./b.sh\nlog \"hallo\"\n"),
+    );
+    files.insert(
+        PathBuf::from("a.sh"),
+        String::from("log() {\n    echo \"$1\"\n}\n"),
+    );
+    files.insert(
+        PathBuf::from("b.sh"),
+        String::from("cat <<EOF\nlog() {\nEOF\nfunction log {\n    echo \"$1\" >&2\n}\n"),
+    );
+    let args = Args {
+        normalize_functions: true,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!(1, file.warnings.len());
+    assert_eq!(
+        "function `log` is defined more than once, first at ././a.sh:1",
+        file.warnings[0].message
+    );
+    assert_eq!(PathBuf::from("././b.sh"), file.warnings[0].location.path);
+    assert_eq!(3, file.warnings[0].location.line_number);
+
+    let args = Args {
+        strict: true,
+        ..args
+    };
+    assert!(matches!(
+        BashFile::resolve_with_loader("main.sh".into(), &args, &files),
+        Err(Error::DuplicateFunction { .. })
+    ));
+}
+
+#[test]
+fn resolving_byte_order_mark() {
+    let file = BashFile::resolve("./tests/bom.sh".into(), &Args::default()).unwrap();
+
+    let expected = r#"print() {
+    echo "$1"
+}
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(1, file.warnings.len());
+    assert_eq!(
+        PathBuf::from("./tests/./bash/bom_utils.sh"),
+        file.warnings[0].location.path
+    );
+}
+
+#[test]
+fn resolving_quoted_comment() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import \"./my scripts/util.sh\"\n# import './my scripts/more.sh'\n# import ./my scripts/other.sh\n"),
+    );
+    files.insert(
+        PathBuf::from("my scripts/util.sh"),
+        String::from("echo util\n"),
+    );
+    files.insert(
+        PathBuf::from("my scripts/more.sh"),
+        String::from("echo more\n"),
+    );
+    files.insert(
+        PathBuf::from("my scripts/other.sh"),
+        String::from("echo other\n"),
+    );
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+
+    assert_eq!("echo util\necho more\necho other\n", file.to_string());
+    assert!(file.warnings.is_empty());
+}
+
+#[test]
+fn explaining_imports() {
+    let file = BashFile::new("./tests/unresolved.sh".into())
+        .load(&Args::default(), &DiskLoader)
+        .unwrap()
+        .load_dependents(&Args::default(), &DiskLoader)
+        .unwrap();
+
+    let expected = "./tests/unresolved.sh:1: `./bash/one_utils.sh` resolved to ./tests/./bash/one_utils.sh
+./tests/unresolved.sh:2: `./bash/missing_utils.sh` is not resolved, file not found: ./tests/./bash/missing_utils.sh
+";
+    assert_eq!(expected, file.explain(PathStyle::AsTyped));
+}
+
+#[test]
+fn resolving_wide_diamond() {
+    struct CountingLoader {
+        files: HashMap<PathBuf, String>,
+        loads: std::cell::Cell<usize>,
+    }
+
+    impl SourceLoader for CountingLoader {
+        fn load(&self, path: &Path) -> Result<Vec<u8>, Error> {
+            self.loads.set(self.loads.get() + 1);
+            self.files.load(path)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files.exists(path)
+        }
+    }
+
+    let mut files = HashMap::new();
+    let mut main = String::new();
+    for i in 0..20 {
+        main.push_str(&format!("# import ./m{}.sh\n", i));
+        files.insert(
+            PathBuf::from(format!("m{}.sh", i)),
+            format!("# import ./shared.sh\necho m{}\n", i),
+        );
+    }
+    files.insert(PathBuf::from("main.sh"), main);
+    files.insert(
+        PathBuf::from("shared.sh"),
+        String::from("# import ./deep.sh\necho shared\n"),
+    );
+    files.insert(PathBuf::from("deep.sh"), String::from("echo deep\n"));
+    let loader = CountingLoader {
+        files,
+        loads: std::cell::Cell::new(0),
+    };
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &loader).unwrap();
+
+    // main.sh, the 20 middle files, shared.sh and deep.sh are loaded once
+    assert_eq!(23, loader.loads.get());
+    let output = file.to_string();
+    assert_eq!(20, output.matches("echo deep\necho shared\n").count());
+    assert!(output.starts_with("echo deep\necho shared\necho m0\necho deep\n"));
+    assert_eq!(
+        Some((Path::new("././deep.sh"), 0)),
+        source_map::find(file.spans(), 57)
+    );
+}
+
+#[test]
+fn resolving_flatten() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from(
+            "#!/bin/bash\n# import ./a.sh\n# import ./b.sh\n# import ./missing.sh\na\nb\n",
+        ),
+    );
+    files.insert(
+        PathBuf::from("a.sh"),
+        String::from("# import ./c.sh\na() {\n    c\n}\n"),
+    );
+    files.insert(
+        PathBuf::from("b.sh"),
+        String::from("# import ./c.sh\nb() {\n    c\n}\n"),
+    );
+    files.insert(
+        PathBuf::from("c.sh"),
+        String::from("c() {\n    echo c\n}\n"),
+    );
+    let args = Args {
+        mode: Mode::Flatten,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+
+    let expected = "#!/bin/bash
+c() {
+    echo c
+}
+a() {
+    c
+}
+b() {
+    c
+}
+# import ./missing.sh
+a
+b
+";
+    assert_eq!(expected, file.to_string());
+    assert_eq!(1, file.warnings.len());
+    assert_eq!(
+        Some((Path::new("././b.sh"), 2)),
+        source_map::find(file.spans(), 8)
+    );
+    assert_eq!(
+        Some((Path::new("main.sh"), 4)),
+        source_map::find(file.spans(), 11)
+    );
+}
+
+#[test]
+fn resolving_root_order() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("#!/bin/bash\nset -euo pipefail\n# import ./a.sh\n# (libraries)\na\n"),
+    );
+    files.insert(
+        PathBuf::from("a.sh"),
+        String::from("a() {\n    echo a\n}\n"),
+    );
+
+    let args = Args {
+        mode: Mode::Flatten,
+        root_order: RootOrder::First,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    let expected = "#!/bin/bash
+set -euo pipefail
+# (libraries)
+a
+a() {
+    echo a
+}
+";
+    assert_eq!(expected, file.to_string());
+
+    let args = Args {
+        mode: Mode::Flatten,
+        root_order: RootOrder::Marker,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    let expected = "#!/bin/bash
+set -euo pipefail
+a() {
+    echo a
+}
+a
+";
+    assert_eq!(expected, file.to_string());
+    assert!(file.warnings.is_empty());
+    assert_eq!(
+        Some((Path::new("./a.sh"), 0)),
+        source_map::find(file.spans(), 2)
+    );
+    assert_eq!(
+        Some((Path::new("main.sh"), 4)),
+        source_map::find(file.spans(), 5)
+    );
+
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("#!/bin/bash\n# import ./a.sh\na\n"),
+    );
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!("#!/bin/bash\na() {\n    echo a\n}\na\n", file.to_string());
+    assert_eq!(1, file.warnings.len());
+}
+
+#[test]
+fn resolving_aliases() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("src/main.sh"),
+        String::from("# import @std/logging.sh\nlog \"hallo\"\n"),
+    );
+    files.insert(
+        PathBuf::from("vendor/std/logging.sh"),
+        String::from("log() {\n    echo \"$1\"\n}\n"),
+    );
+    let args = Args {
+        aliases: BTreeMap::from([(String::from("@std"), PathBuf::from("vendor/std"))]),
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve_with_loader("src/main.sh".into(), &args, &files).unwrap();
+    assert_eq!(
+        "log() {\n    echo \"$1\"\n}\nlog \"hallo\"\n",
+        file.to_string()
+    );
+
+    assert_eq!(
+        Some(PathBuf::from("vendor/std")),
+        args.expand_alias("@std").unwrap()
+    );
+    assert_eq!(None, args.expand_alias("./std/logging.sh").unwrap());
+    assert!(matches!(
+        args.expand_alias("@other/logging.sh"),
+        Err(Error::UnknownAlias(name)) if name == "@other"
+    ));
+    let err =
+        BashFile::resolve_with_loader("src/main.sh".into(), &Args::default(), &files).unwrap_err();
+    assert_eq!("unknown_alias", err.kind());
+
+    // `source` lines use the aliases too
+    files.insert(
+        PathBuf::from("src/main.sh"),
+        String::from("source @std/logging.sh\nlog \"hallo\"\n"),
+    );
+    let args = Args {
+        root_path: Some("src/main.sh".into()),
+        replace_source: true,
+        ..args
+    };
+    let file = BashFile::resolve_with_loader("src/main.sh".into(), &args, &files).unwrap();
+    assert_eq!(
+        "log() {\n    echo \"$1\"\n}\nlog \"hallo\"\n",
+        file.to_string()
+    );
+}
+
+#[test]
+fn config_aliases_relative_to_config() {
+    let config = Config::load(Path::new("tests/alias_config.toml")).unwrap();
+    let args = config.into_args(None).unwrap();
+
+    assert_eq!(
+        Some(PathBuf::from("tests/./bash/one_utils.sh")),
+        args.expand_alias("@utils/one_utils.sh").unwrap()
+    );
+}
+
+#[test]
+fn resolving_manifest() {
+    let path = PathBuf::from("target/resolving_manifest.txt");
+    let _ = std::fs::remove_file(&path);
+    let args = Args {
+        manifest: Some(path.clone()),
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve("./tests/one.sh".into(), &args).unwrap();
+    // the binary writes the manifest, the library only builds it
+    assert!(!path.exists());
+    assert_eq!(3, file.manifest.unwrap().lines().count());
+}
+
+#[test]
+fn resolving_in_memory() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("src/main.sh"),
+        String::from("# import ./lib/greet.sh\ngreet \"hallo\"\n"),
+    );
+    files.insert(
+        PathBuf::from("src/lib/greet.sh"),
+        String::from("# import ./missing.sh\ngreet() {\n    echo \"$1\"\n}\n"),
+    );
+
+    let file =
+        BashFile::resolve_with_loader("./src/main.sh".into(), &Args::default(), &files).unwrap();
+
+    let expected = "# import ./missing.sh\ngreet() {\n    echo \"$1\"\n}\ngreet \"hallo\"\n";
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(1, file.warnings.len());
+}
+
+#[test]
+fn resolving_missing_banner_file() {
+    let args = Args {
+        banner_file: Some("./tests/missing_banner.txt".into()),
+        ..Args::default()
+    };
+    let err = BashFile::resolve("./tests/one.sh".into(), &args).unwrap_err();
+
+    assert!(matches!(err, Error::IoWithPath { .. }));
+    assert!(err.to_string().contains("./tests/missing_banner.txt"));
+}
+
+#[test]
+fn resolving_import_markers_comment_prefix() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.lua"),
+        String::from("-- import ./lib/a.sh\nprint(a)\n"),
+    );
+    files.insert(PathBuf::from("lib/a.sh"), String::from("a = 1\n"));
+    let args = Args {
+        comment_prefix: String::from("--"),
+        keep_import_markers: true,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve_with_loader("main.lua".into(), &args, &files).unwrap();
+    assert_eq!(
+        "-- (inlined ./lib/a.sh)\na = 1\nprint(a)\n",
+        file.to_string()
+    );
+}
+
+#[test]
+fn resolving_import_markers() {
+    let args = Args {
+        keep_import_markers: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/one.sh".into(), &args).unwrap();
+
+    let expected = r#"# (inlined ./bash/one_utils.sh)
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+# (inlined ./bash/one_more_utils.sh)
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(None, source_map::find(file.spans(), 0));
+    assert_eq!(
+        Some((Path::new("./tests/./bash/one_more_utils.sh"), 0)),
+        source_map::find(file.spans(), 5)
+    );
+}
+
+#[test]
+fn resolving_kept_directives() {
+    let args = Args {
+        keep_directives: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/one.sh".into(), &args).unwrap();
+
+    let expected = r#"# import ./bash/one_utils.sh
+# (begin ./bash/one_utils.sh)
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+# (end ./bash/one_utils.sh)
+# import ./bash/one_more_utils.sh
+# (begin ./bash/one_more_utils.sh)
+print() {
+    echo "$1"
+}
+# (end ./bash/one_more_utils.sh)
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/one.sh"), 0)),
+        source_map::find(file.spans(), 0)
+    );
+    assert_eq!(None, source_map::find(file.spans(), 1));
+    assert_eq!(
+        Some((Path::new("./tests/./bash/one_more_utils.sh"), 0)),
+        source_map::find(file.spans(), 8)
+    );
+
+    let mut files = HashMap::new();
+    files.insert(PathBuf::from("main.sh"), file.to_string());
+    let again = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!(file.to_string(), again.to_string());
+}
+
+#[test]
+fn resolving_source_ref() {
+    let args = Args {
+        keep_source_ref: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/one.sh".into(), &args).unwrap();
+
+    let expected = r#"# import ./bash/one_utils.sh (inlined)
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+# import ./bash/one_more_utils.sh (inlined)
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/one.sh"), 0)),
+        source_map::find(file.spans(), 0)
+    );
+    assert_eq!(
+        Some((Path::new("./tests/./bash/one_more_utils.sh"), 0)),
+        source_map::find(file.spans(), 5)
+    );
+
+    let mut files = HashMap::new();
+    files.insert(PathBuf::from("main.sh"), file.to_string());
+    let again = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!(file.to_string(), again.to_string());
+    assert!(again.warnings.is_empty());
+}
+
+#[test]
+fn source_ref_of_source_import() {
+    let import = ImportStatement {
+        line: String::from("    source ./utils.sh"),
+        text: String::from("./utils.sh"),
+        path: PathBuf::from("./utils.sh"),
+        style: ImportStyle::Source,
+        resolved: Vec::new(),
+        unresolved: None,
+        trailing: None,
+        continued: 0,
+        region: None,
+        line_number: 0,
+    };
+
+    assert_eq!(
+        "    # source ./utils.sh (inlined)",
+        source_ref(&import, &Args::default())
+    );
+}
+
+#[test]
+fn resolving_outside_root_dir() {
+    let args = Args {
+        root_dir: Some("./tests".into()),
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/one.sh".into(), &args).unwrap();
+    assert!(file.warnings.is_empty());
+
+    let args = Args {
+        root_dir: Some("./tests/unused".into()),
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/one.sh".into(), &args).unwrap();
+    assert_eq!(2, file.warnings.len());
+    assert_eq!(
+        "import ./tests/./bash/one_utils.sh is outside the root directory ./tests/unused",
+        file.warnings[0].message
+    );
+
+    let args = Args {
+        strict: true,
+        ..args
+    };
+    match BashFile::resolve("./tests/one.sh".into(), &args).unwrap_err() {
+        Error::OutsideRoot { location, .. } => assert_eq!(0, location.line_number),
+        err => panic!("expected outside root error, got: {}", err),
+    }
+}
+
+#[test]
+fn resolving_empty_import() {
+    let file = BashFile::resolve("./tests/two.sh".into(), &Args::default()).unwrap();
+    assert_eq!(1, file.warnings.len());
+    assert_eq!(
+        "import ./tests/./bash/./two_empty.bash is empty",
+        file.warnings[0].message
+    );
+    assert_eq!(1, file.warnings[0].location.line_number);
+
+    let args = Args {
+        strict: true,
+        ..Args::default()
+    };
+    assert!(matches!(
+        BashFile::resolve("./tests/two.sh".into(), &args),
+        Err(Error::EmptyImport { .. })
+    ));
+}
+
+#[test]
+fn resolving_keep_going() {
+    let args = Args {
+        strict: true,
+        ..Args::default()
+    };
+    assert!(matches!(
+        BashFile::resolve("./tests/keep_going.sh".into(), &args),
+        Err(Error::Unresolved { .. })
+    ));
+
+    let args = Args {
+        strict: true,
+        keep_going: true,
+        ..Args::default()
+    };
+    let errors = match BashFile::resolve("./tests/keep_going.sh".into(), &args) {
+        Err(Error::Multiple(errors)) => errors,
+        other => panic!("expected multiple errors, got: {:?}", other),
+    };
+    let kinds: Vec<_> = errors.iter().map(Error::kind).collect();
+    assert_eq!(vec!["unresolved", "empty_import", "unresolved"], kinds);
+    assert_eq!(Some(4), errors[2].position().1);
+
+    // a cycle still stops immediately
+    assert!(matches!(
+        BashFile::resolve("./tests/circular.sh".into(), &args),
+        Err(Error::Circular { .. })
+    ));
+}
+
+#[test]
+fn source_paths() {
+    assert_eq!(("./a.sh", ""), split_source_path("./a.sh"));
+    assert_eq!(
+        ("./a.sh", " || exit 1"),
+        split_source_path("./a.sh || exit 1")
+    );
+    assert_eq!(
+        ("./a.sh", "&& echo ok"),
+        split_source_path("./a.sh&& echo ok")
+    );
+    assert_eq!(
+        ("./my file.sh", "; echo ok"),
+        split_source_path("\"./my file.sh\"; echo ok")
+    );
+    assert_eq!(("'./a.sh", ""), split_source_path("'./a.sh"));
+
+    assert_eq!(None, trailing_command(""));
+    assert_eq!(None, trailing_command(" arg1 arg2"));
+    assert_eq!(None, trailing_command(" # comment || not a command"));
+    assert_eq!(None, trailing_command(";"));
+    assert_eq!(
+        Some(String::from("(exit $?) || exit 1")),
+        trailing_command(" || exit 1")
+    );
+    assert_eq!(
+        Some(String::from("(exit $?) && echo ok")),
+        trailing_command(" arg && echo ok ")
+    );
+    assert_eq!(Some(String::from("echo ok")), trailing_command("; echo ok"));
+    assert_eq!(
+        Some(String::from("echo ok")),
+        trailing_command(" & echo ok")
+    );
+    assert_eq!(None, trailing_command(" &"));
+}
+
+#[test]
+fn resolving_source_operators() {
+    let args = Args {
+        root_path: Some("./tests/source_operators.sh".into()),
+        replace_source: true,
+        replace_comment: false,
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve("./tests/source_operators.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+(exit $?) || exit 1
+print() {
+    echo "$1"
+}
+(exit $?) && echo "loaded"
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/source_operators.sh"), 1)),
+        source_map::find(file.spans(), 7)
+    );
+}
+
+#[test]
+fn resolving_separator() {
+    let args = Args {
+        separator: String::from("# ----"),
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/two.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+
+super_yell() {
+    yell "$1 !!!!!!"
+}
+# ----
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+super_yell "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(None, source_map::find(file.spans(), 8));
+    assert_eq!(
+        Some((Path::new("./tests/./bash/one_more_utils.sh"), 0)),
+        source_map::find(file.spans(), 9)
+    );
+}
+
+#[test]
+fn parent_dirs() {
+    assert_eq!(PathBuf::from("."), parent_dir(Path::new("one.sh")));
+    assert_eq!(
+        PathBuf::from("./tests"),
+        parent_dir(Path::new("./tests/one.sh"))
+    );
+    assert_eq!(PathBuf::from("."), parent_dir(Path::new("/")));
+}
+
+#[test]
+fn resolving_import_base() {
+    let args = Args {
+        import_base: Some("./tests/bash".into()),
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/import_base.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string())
+}
+
+#[test]
+fn import_keywords() {
+    assert_eq!(Some("./a.sh"), strip_import_keyword(" import ./a.sh"));
+    assert_eq!(Some("./a.sh"), strip_import_keyword("import ./a.sh"));
+    assert_eq!(Some("./a.sh"), strip_import_keyword("import\t./a.sh"));
+    assert_eq!(None, strip_import_keyword("important-note"));
+    assert_eq!(None, strip_import_keyword(" imports ./a.sh"));
+    assert_eq!(None, strip_import_keyword(" import"));
+    assert_eq!(None, strip_import_keyword("  import ./a.sh"));
+}
+
+#[test]
+fn marked_import_keyword() {
+    assert_eq!(
+        Some((ImportMarker::Plain, "./a.sh")),
+        strip_marked_import_keyword(" import ./a.sh")
+    );
+    assert_eq!(
+        Some((ImportMarker::Forced, "./a.sh")),
+        strip_marked_import_keyword(" import! ./a.sh")
+    );
+    assert_eq!(
+        Some((ImportMarker::Optional, "./a.sh")),
+        strip_marked_import_keyword("import? ./a.sh")
+    );
+    assert_eq!(None, strip_marked_import_keyword(" import!./a.sh"));
+    assert_eq!(None, strip_marked_import_keyword(" import!? ./a.sh"));
+}
+
+#[test]
+fn resolving_marked_imports() {
+    let mut files = HashMap::new();
+    files.insert(
+        PathBuf::from("main.sh"),
+        String::from("# import ./a.sh\n# import! ./b.sh\n# import? ./c.sh\n"),
+    );
+    files.insert(PathBuf::from("a.sh"), String::from("echo a"));
+    files.insert(PathBuf::from("b.sh"), String::from("echo b"));
+    files.insert(PathBuf::from("c.sh"), String::from("echo c"));
+
+    let file = BashFile::resolve_with_loader("main.sh".into(), &Args::default(), &files).unwrap();
+    assert_eq!("echo a\necho b\n# import? ./c.sh\n", file.to_string());
+
+    let args = Args {
+        replace_comment: false,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!(
+        "# import ./a.sh\necho b\n# import? ./c.sh\n",
+        file.to_string()
+    );
+
+    let args = Args {
+        replace_comment: false,
+        optional_imports: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve_with_loader("main.sh".into(), &args, &files).unwrap();
+    assert_eq!("# import ./a.sh\necho b\necho c\n", file.to_string());
+}
+
+#[test]
+fn two_level_tree() {
+    let file = BashFile::new("./tests/two.sh".into())
+        .load(&Args::default(), &DiskLoader)
+        .unwrap()
+        .load_dependents(&Args::default(), &DiskLoader)
+        .unwrap();
+
+    let expected = "./tests/two.sh
+  1: ./bash/two_utils.sh (comment)
+    ./tests/./bash/two_utils.sh
+      1: ./one_utils.sh (comment)
+        ./tests/./bash/./one_utils.sh
+      2: ./two_empty.bash (comment)
+        ./tests/./bash/./two_empty.bash
+  2: ./bash/one_more_utils.sh (comment)
+    ./tests/./bash/one_more_utils.sh
+";
+
+    assert_eq!(expected, file.tree(PathStyle::AsTyped));
+
+    let expected = "tests/two.sh
+  1: ./bash/two_utils.sh (comment)
+    tests/bash/two_utils.sh
+      1: ./one_utils.sh (comment)
+        tests/bash/one_utils.sh
+      2: ./two_empty.bash (comment)
+        tests/bash/two_empty.bash
+  2: ./bash/one_more_utils.sh (comment)
+    tests/bash/one_more_utils.sh
+";
+    assert_eq!(expected, file.tree(PathStyle::Relative));
+}
+
+#[test]
+fn path_styles() {
+    let current_dir = std::env::current_dir().unwrap();
+    let path = Path::new("./tests/./bash/../one.sh");
+
+    assert_eq!(path, PathStyle::AsTyped.render(path));
+    assert_eq!(Path::new("tests/one.sh"), PathStyle::Relative.render(path));
+    assert_eq!(
+        current_dir.join("tests/one.sh"),
+        PathStyle::Absolute.render(path)
+    );
+    assert_eq!(
+        Path::new("tests/one.sh"),
+        PathStyle::Relative.render(&current_dir.join("tests/one.sh"))
+    );
+
+    assert_eq!(
+        PathBuf::from("../../c/d.sh"),
+        relative_to(Path::new("/a/c/d.sh"), Path::new("/a/b/e"))
+    );
+    assert_eq!(
+        PathBuf::from("."),
+        relative_to(Path::new("/a/b"), Path::new("/a/b"))
+    );
+}
+
+#[test]
+fn resolving_blank_between_files() {
+    let args = Args {
+        blank_between_files: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/two.sh".into(), &args).unwrap();
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+super_yell() {
+    yell "$1 !!!!!!"
+}
+
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+super_yell "hallo"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/./bash/two_utils.sh"), 3)),
+        source_map::find(file.spans(), 4)
+    );
+    assert_eq!(None, source_map::find(file.spans(), 7));
+    assert_eq!(
+        Some((Path::new("./tests/./bash/one_more_utils.sh"), 0)),
+        source_map::find(file.spans(), 8)
+    );
+    assert_eq!(
+        Some((Path::new("./tests/two.sh"), 2)),
+        source_map::find(file.spans(), 11)
+    );
+}
+
+#[test]
+fn resolving_injected() {
+    let args = Args {
+        prepend: Some("./tests/inject/prologue.sh".into()),
+        append: Some("./tests/inject/epilogue.sh".into()),
+        resolve_injected: true,
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/guard.sh".into(), &args).unwrap();
+
+    let expected = r#"#!/usr/bin/env bash
+set -euo pipefail
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+greet() {
+    yell "hello $1"
+}
+
+# entry point
+greet "$1"
+echo done
+print() {
+    echo "$1"
+}
+print "done"
+"#;
+
+    assert_eq!(expected, file.to_string());
+    assert_eq!(
+        Some((Path::new("./tests/inject/prologue.sh"), 0)),
+        source_map::find(file.spans(), 1)
+    );
+    assert_eq!(
+        Some((Path::new("./tests/guard.sh"), 3)),
+        source_map::find(file.spans(), 6)
+    );
+    assert_eq!(
+        Some((Path::new("./tests/inject/epilogue.sh"), 1)),
+        source_map::find(file.spans(), 16)
+    );
+}
+
+#[test]
+fn injecting_verbatim() {
+    let args = Args {
+        append: Some("./tests/inject/epilogue.sh".into()),
+        ..Args::default()
+    };
+    let file = BashFile::resolve("./tests/main.sh".into(), &args).unwrap();
+
+    assert!(file
+        .to_string()
+        .ends_with("}\n# import ../bash/one_more_utils.sh\nprint \"done\"\n"));
+}
+
+#[test]
+fn resolving_shebang() {
+    let args = Args {
+        shebang: Some(String::from("#!/bin/sh")),
+        ..Args::default()
+    };
+
+    let file = BashFile::resolve("./tests/shebang.sh".into(), &args).unwrap();
+    assert!(file.to_string().starts_with("#!/bin/sh\nyell() {\n"));
+
+    let file = BashFile::resolve("./tests/one.sh".into(), &args).unwrap();
+    assert!(file.to_string().starts_with("#!/bin/sh\nyell() {\n"));
+    assert_eq!(
+        Some((Path::new("./tests/./bash/one_utils.sh"), 0)),
+        source_map::find(file.spans(), 1)
+    );
+
+    assert!(shebang_line("/bin/sh").is_err());
+    assert!(shebang_line("#!/bin/sh\necho").is_err());
+}
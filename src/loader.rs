@@ -0,0 +1,193 @@
+//! reading of the source files, from disk or from memory
+
+use crate::Error;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// where the source files are read from
+pub trait SourceLoader {
+    /// the raw contents of the file
+    fn load(&self, path: &Path) -> Result<Vec<u8>, Error>;
+
+    /// whether the file or directory exists
+    fn exists(&self, path: &Path) -> bool;
+
+    /// whether the path is a directory, only directories of a loader that supports them can be imported
+    fn is_dir(&self, _path: &Path) -> bool {
+        false
+    }
+
+    /// the paths of the files and directories in the directory, walked by glob and directory imports
+    fn read_dir(&self, _path: &Path) -> Result<Vec<PathBuf>, Error> {
+        Ok(Vec::new())
+    }
+
+    /// the size of the file in bytes, if it is known without loading the file
+    fn size(&self, _path: &Path) -> Option<u64> {
+        None
+    }
+
+    /// whether the file can be read, checked before loading with `--require-readable`
+    fn is_readable(&self, _path: &Path) -> bool {
+        true
+    }
+
+    /// the identity of the file, paths with the same identity are the same file
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        if !self.exists(path) {
+            return Err(Error::NotFound(path.to_path_buf()));
+        }
+        Ok(normalize(path))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// loads the files from disk
+pub struct DiskLoader;
+
+impl SourceLoader for DiskLoader {
+    fn load(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        std::fs::read(path).map_err(|source| Error::IoWithPath {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let with_path = |source| Error::IoWithPath {
+            path: path.to_path_buf(),
+            source,
+        };
+        std::fs::read_dir(path)
+            .map_err(with_path)?
+            .map(|entry| entry.map(|x| x.path()).map_err(with_path))
+            .collect()
+    }
+
+    fn size(&self, path: &Path) -> Option<u64> {
+        std::fs::metadata(path).ok().map(|x| x.len())
+    }
+
+    #[cfg(unix)]
+    fn is_readable(&self, path: &Path) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::metadata(path).is_ok_and(|x| x.permissions().mode() & 0o444 != 0)
+    }
+
+    #[cfg(not(unix))]
+    fn is_readable(&self, path: &Path) -> bool {
+        std::fs::File::open(path).is_ok()
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, Error> {
+        std::fs::canonicalize(path).map_err(|source| Error::IoWithPath {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// loads the files from memory, `./a/b.sh`, `a/./b.sh` and `a/b.sh` are the same file
+///
+/// the directories are the parent directories of the files
+impl SourceLoader for HashMap<PathBuf, String> {
+    fn load(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let path = normalize(path);
+        self.iter()
+            .find(|(key, _)| normalize(key) == path)
+            .map(|(_, contents)| contents.clone().into_bytes())
+            .ok_or(Error::NotFound(path))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        self.keys().any(|key| normalize(key).starts_with(&path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        !self.read_dir(path).unwrap_or_default().is_empty()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        Ok(children(self.keys(), path))
+    }
+}
+
+/// the files and directories directly in the directory, from the paths of all the files
+///
+/// the paths start with `directory` as given, like the entries of `std::fs::read_dir`
+pub(crate) fn children<P: AsRef<Path>>(
+    files: impl IntoIterator<Item = P>,
+    directory: &Path,
+) -> Vec<PathBuf> {
+    let prefix = normalize(directory);
+    let mut paths: Vec<_> = files
+        .into_iter()
+        .filter_map(|file| {
+            let file = normalize(file.as_ref());
+            let name = file.strip_prefix(&prefix).ok()?.components().next()?;
+            Some(directory.join(name))
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// the path without `.` components
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|x| *x != Component::CurDir)
+        .collect()
+}
+
+#[test]
+fn memory_loader() {
+    let mut files = HashMap::new();
+    files.insert(PathBuf::from("src/main.sh"), String::from("echo hallo"));
+
+    assert!(files.exists(Path::new("./src/./main.sh")));
+    assert!(!files.exists(Path::new("./main.sh")));
+    assert_eq!(
+        b"echo hallo".to_vec(),
+        files.load(Path::new("./src/main.sh")).unwrap()
+    );
+    assert_eq!(
+        PathBuf::from("src/main.sh"),
+        files.canonicalize(Path::new("./src/main.sh")).unwrap()
+    );
+    assert!(matches!(
+        files.load(Path::new("./other.sh")),
+        Err(Error::NotFound(_))
+    ));
+}
+
+#[test]
+fn memory_loader_directories() {
+    let mut files = HashMap::new();
+    files.insert(PathBuf::from("src/main.sh"), String::from("echo hallo"));
+    files.insert(PathBuf::from("src/lib/a.sh"), String::from("a"));
+    files.insert(PathBuf::from("src/lib/b.sh"), String::from("b"));
+
+    assert!(files.is_dir(Path::new("./src")));
+    assert!(files.is_dir(Path::new(".")));
+    assert!(!files.is_dir(Path::new("./src/main.sh")));
+    assert!(!files.is_dir(Path::new("./other")));
+    assert_eq!(
+        vec![PathBuf::from("./src/lib"), PathBuf::from("./src/main.sh")],
+        files.read_dir(Path::new("./src")).unwrap()
+    );
+    assert_eq!(
+        vec![PathBuf::from("./src")],
+        files.read_dir(Path::new(".")).unwrap()
+    );
+}
@@ -0,0 +1,185 @@
+//! reporting of errors to stderr
+
+use crate::{Error, ErrorFormat, Location, Warning};
+use std::io::IsTerminal;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+/// whether stderr should be colored, respects `NO_COLOR`
+fn use_color() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty());
+    !no_color && std::io::stderr().is_terminal()
+}
+
+/// print the error to stderr, in the given format
+pub fn error(err: &Error, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprint!("{}", format_error(err, use_color())),
+        ErrorFormat::Json => eprintln!("{}", format_json_error(err)),
+    }
+}
+
+/// print the warnings to stderr
+pub fn warnings(warnings: &[Warning]) {
+    let color = use_color();
+    for warning in warnings {
+        eprint!("{}", format_warning(warning, color));
+    }
+}
+
+fn format_warning(warning: &Warning, color: bool) -> String {
+    let (yellow, blue, reset) = if color {
+        (YELLOW, BLUE, RESET)
+    } else {
+        ("", "", "")
+    };
+
+    format!(
+        "{}warning{}: {}\n{}",
+        yellow,
+        reset,
+        warning.message,
+        format_snippet(&warning.location, blue, reset)
+    )
+}
+
+fn format_error(err: &Error, color: bool) -> String {
+    let (red, blue, reset) = if color {
+        (RED, BLUE, RESET)
+    } else {
+        ("", "", "")
+    };
+
+    let mut output = String::new();
+    if let Error::Multiple(errors) = err {
+        for err in errors {
+            output.push_str(&format_error(err, color));
+        }
+    }
+    output.push_str(&format!("{}error{}: {}\n", red, reset, err));
+    if let Error::Circular {
+        location: Some(location),
+        ..
+    }
+    | Error::SelfImport(location)
+    | Error::OutsideRoot { location, .. }
+    | Error::EmptyImport { location, .. }
+    | Error::Unresolved { location, .. }
+    | Error::DuplicateFunction { location, .. }
+    | Error::MissingInterpreter { location, .. }
+    | Error::RegionNotFound { location, .. } = err
+    {
+        output.push_str(&format_snippet(location, blue, reset));
+    }
+    output
+}
+
+/// the error as a JSON object with the `kind`, `message`, `path` and `line`, and the `cycle` of a circular import,
+/// the errors collected with `--keep-going` are one object per line
+fn format_json_error(err: &Error) -> String {
+    if let Error::Multiple(errors) = err {
+        let errors: Vec<_> = errors.iter().map(format_json_error).collect();
+        return errors.join("\n");
+    }
+    let (path, line) = err.position();
+    let mut output = format!(
+        "{{\"kind\":{},\"message\":{},\"path\":{},\"line\":{}",
+        json_string(err.kind()),
+        json_string(&err.to_string()),
+        path.map_or_else(
+            || String::from("null"),
+            |x| json_string(&x.to_string_lossy())
+        ),
+        line.map_or_else(|| String::from("null"), |x| x.to_string()),
+    );
+    if let Error::Circular { cycle, .. } = err {
+        let cycle: Vec<_> = cycle
+            .iter()
+            .map(|x| json_string(&x.to_string_lossy()))
+            .collect();
+        output.push_str(&format!(",\"cycle\":[{}]", cycle.join(",")));
+    }
+    output.push('}');
+    output
+}
+
+/// the text as a quoted JSON string
+fn json_string(text: &str) -> String {
+    let mut output = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// a caret underlined snippet of the line
+fn format_snippet(location: &Location, blue: &str, reset: &str) -> String {
+    let line_number = (location.line_number + 1).to_string();
+    let padding = " ".repeat(line_number.len());
+    let start = location.line.len() - location.line.trim_start().len();
+    let carets = "^".repeat(location.line.trim().len().max(1));
+
+    format!(
+        "{pad}{b}-->{r} {path}:{line_number}\n{pad} {b}|{r}\n{b}{line_number} |{r} {line}\n{pad} {b}|{r} {indent}{b}{carets}{r}\n",
+        pad = padding,
+        b = blue,
+        r = reset,
+        path = location.path.display(),
+        line_number = line_number,
+        line = location.line,
+        indent = " ".repeat(start),
+        carets = carets,
+    )
+}
+
+#[test]
+fn circular_snippet() {
+    let err = Error::Circular {
+        location: Some(Location {
+            path: "tests/bash/circular_2_utils.sh".into(),
+            line_number: 0,
+            line: String::from("# import ./circular_1_utils.sh"),
+        }),
+        cycle: Vec::new(),
+    };
+
+    let expected = r#"error: Circular import found
+ --> tests/bash/circular_2_utils.sh:1
+  |
+1 | # import ./circular_1_utils.sh
+  | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+"#;
+
+    assert_eq!(expected, format_error(&err, false))
+}
+
+#[test]
+fn json_error() {
+    let err = Error::Circular {
+        location: Some(Location {
+            path: "tests/bash/circular_2_utils.sh".into(),
+            line_number: 0,
+            line: String::from("# import ./circular_1_utils.sh"),
+        }),
+        cycle: vec!["/a.sh".into(), "/b \"c\".sh".into(), "/a.sh".into()],
+    };
+
+    let expected = r#"{"kind":"circular","message":"Circular import found","path":"tests/bash/circular_2_utils.sh","line":1,"cycle":["/a.sh","/b \"c\".sh","/a.sh"]}"#;
+    assert_eq!(expected, format_json_error(&err));
+
+    let expected = r#"{"kind":"missing_root","message":"no root file given, pass a file or a config with a `root_path`","path":null,"line":null}"#;
+    assert_eq!(expected, format_json_error(&Error::MissingRoot));
+    assert_eq!(r#""a\nb\u0001""#, json_string("a\nb\u{1}"));
+}
@@ -0,0 +1,83 @@
+//! linting of the bundle with shellcheck
+
+use crate::{source_map, Error, SourceSpan};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// run shellcheck on the bundle, printing its diagnostics to stderr with the original file locations
+///
+/// fails if shellcheck reports errors
+pub fn run(output: &str, spans: &[SourceSpan]) -> Result<(), Error> {
+    let mut command = Command::new("shellcheck");
+    command.args(["--format", "gcc"]);
+    if !output.starts_with("#!") {
+        command.arg("--shell=bash");
+    }
+
+    let mut child = command
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => Error::Shellcheck(String::from(
+                "`shellcheck` is not installed or not on the PATH",
+            )),
+            _ => Error::Io(err),
+        })?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(output.as_bytes())?;
+    let result = child.wait_with_output()?;
+
+    let mut errors = 0;
+    for line in String::from_utf8_lossy(&result.stdout).lines() {
+        if line.contains(": error: ") {
+            errors += 1;
+        }
+        eprintln!("{}", translate(line, spans));
+    }
+
+    if errors > 0 {
+        return Err(Error::Shellcheck(format!(
+            "shellcheck found {} error(s)",
+            errors
+        )));
+    }
+    Ok(())
+}
+
+/// replace the `-:<line>:` location of a gcc formatted diagnostic with the source location
+fn translate(line: &str, spans: &[SourceSpan]) -> String {
+    let location = line.strip_prefix("-:").and_then(|rest| {
+        let (number, rest) = rest.split_once(':')?;
+        let number: usize = number.parse().ok()?;
+        let (path, source_line) = source_map::find(spans, number.checked_sub(1)?)?;
+        Some(format!("{}:{}:{}", path.display(), source_line + 1, rest))
+    });
+
+    location.unwrap_or_else(|| String::from(line))
+}
+
+#[test]
+fn translate_location() {
+    let spans = vec![SourceSpan {
+        output_start: 3,
+        output_end: 6,
+        source_path: "utils.sh".into(),
+        source_start: 10,
+    }];
+
+    assert_eq!(
+        "utils.sh:12:5: error: message [SC1000]",
+        translate("-:5:5: error: message [SC1000]", &spans)
+    );
+    assert_eq!(
+        "-:1:5: error: message [SC1000]",
+        translate("-:1:5: error: message [SC1000]", &spans)
+    );
+}
@@ -0,0 +1,166 @@
+//! mapping of the lines in the bundled output back to the source files
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+/// output lines `output_start..output_end` come from `source_path`, starting at line `source_start`
+///
+/// all line numbers start at 0
+pub struct SourceSpan {
+    pub output_start: usize,
+    pub output_end: usize,
+    pub source_path: PathBuf,
+    pub source_start: usize,
+}
+
+impl SourceSpan {
+    fn len(&self) -> usize {
+        self.output_end - self.output_start
+    }
+
+    /// the span moved `offset` lines down in the output
+    pub fn offset(mut self, offset: usize) -> SourceSpan {
+        self.output_start += offset;
+        self.output_end += offset;
+        self
+    }
+}
+
+/// add the mapping of a single output line, merging it with the last span if it continues it
+pub fn push_line(
+    spans: &mut Vec<SourceSpan>,
+    output_line: usize,
+    source_path: &Path,
+    source_line: usize,
+) {
+    if let Some(last) = spans.last_mut() {
+        if last.source_path == source_path
+            && last.output_end == output_line
+            && last.source_start + last.len() == source_line
+        {
+            last.output_end += 1;
+            return;
+        }
+    }
+
+    spans.push(SourceSpan {
+        output_start: output_line,
+        output_end: output_line + 1,
+        source_path: source_path.to_path_buf(),
+        source_start: source_line,
+    });
+}
+
+/// update the spans for `count` unmapped lines inserted before output line `at`
+pub fn insert_lines(spans: &mut Vec<SourceSpan>, at: usize, count: usize) {
+    let mut updated = Vec::with_capacity(spans.len() + 1);
+    for span in spans.drain(..) {
+        if span.output_start >= at {
+            updated.push(span.offset(count));
+        } else if span.output_end > at {
+            let split = at - span.output_start;
+            updated.push(SourceSpan {
+                output_end: at,
+                ..span.clone()
+            });
+            updated.push(SourceSpan {
+                output_start: at + count,
+                output_end: span.output_end + count,
+                source_start: span.source_start + split,
+                source_path: span.source_path,
+            });
+        } else {
+            updated.push(span);
+        }
+    }
+    *spans = updated;
+}
+
+/// update the spans for the removal of output line `at`
+pub fn remove_line(spans: &mut Vec<SourceSpan>, at: usize) {
+    for span in spans.iter_mut() {
+        if span.output_start > at {
+            span.output_start -= 1;
+            span.output_end -= 1;
+        } else if span.output_end > at {
+            if span.output_start == at {
+                span.source_start += 1;
+            }
+            span.output_end -= 1;
+        }
+    }
+    spans.retain(|span| span.len() > 0);
+}
+
+/// the source file and line of the output line, the spans have to be sorted
+pub fn find(spans: &[SourceSpan], output_line: usize) -> Option<(&Path, usize)> {
+    let index = spans.partition_point(|span| span.output_end <= output_line);
+    let span = spans.get(index)?;
+    if span.output_start > output_line {
+        return None;
+    }
+
+    Some((
+        &span.source_path,
+        span.source_start + output_line - span.output_start,
+    ))
+}
+
+#[test]
+fn insert_lines_splits_span() {
+    let mut spans = vec![SourceSpan {
+        output_start: 0,
+        output_end: 4,
+        source_path: "a.sh".into(),
+        source_start: 0,
+    }];
+
+    insert_lines(&mut spans, 1, 2);
+
+    let expected = vec![
+        SourceSpan {
+            output_start: 0,
+            output_end: 1,
+            source_path: "a.sh".into(),
+            source_start: 0,
+        },
+        SourceSpan {
+            output_start: 3,
+            output_end: 6,
+            source_path: "a.sh".into(),
+            source_start: 1,
+        },
+    ];
+
+    assert_eq!(expected, spans)
+}
+
+#[test]
+fn remove_line_shrinks_span() {
+    let mut spans = vec![
+        SourceSpan {
+            output_start: 0,
+            output_end: 1,
+            source_path: "a.sh".into(),
+            source_start: 0,
+        },
+        SourceSpan {
+            output_start: 1,
+            output_end: 3,
+            source_path: "b.sh".into(),
+            source_start: 0,
+        },
+    ];
+
+    remove_line(&mut spans, 0);
+    remove_line(&mut spans, 0);
+
+    let expected = vec![SourceSpan {
+        output_start: 0,
+        output_end: 1,
+        source_path: "b.sh".into(),
+        source_start: 1,
+    }];
+
+    assert_eq!(expected, spans)
+}
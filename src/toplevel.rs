@@ -0,0 +1,272 @@
+//! heuristic detection of the top level commands in inlined files, with `--strip-toplevel-from-imports`
+
+use crate::function_name;
+use crate::heredoc::Literal;
+
+/// builtins that define something instead of running a command
+const DEFINING_BUILTINS: &[&str] = &["export", "readonly", "declare", "typeset", "alias"];
+/// builtins that load another file at runtime, the definitions may depend on it
+const SOURCING_BUILTINS: &[&str] = &["source", "."];
+/// words that open a block when they are in command position
+const OPENERS: &[&str] = &["if", "case", "for", "select", "while", "until", "{"];
+/// words that close a block when they are in command position
+const CLOSERS: &[&str] = &["fi", "esac", "done", "}"];
+/// words after which the next word is in command position again
+const COMMAND_SEPARATORS: &[&str] = &[
+    ";", ";;", "&&", "||", "|", "&", "then", "do", "else", "elif", "{", "(", "!",
+];
+
+/// for every line, whether it belongs to a top level command,
+/// the lines of function definitions, assignments, comments and `source` lines are kept
+///
+/// a statement continues over heredoc bodies, multiline strings, lines ending in `\`
+/// and the lines of an `if`, `case`, loop or `{ }` block
+pub fn toplevel_commands(lines: &[&str]) -> Vec<bool> {
+    let mut literal = Literal::new();
+    let mut commands = Vec::with_capacity(lines.len());
+    let mut depth = 0;
+    let mut continued = false;
+    let mut in_command = false;
+
+    for line in lines {
+        if literal.is_literal(line) {
+            commands.push(in_command);
+            continue;
+        }
+        if depth > 0 || continued {
+            depth = (depth + nesting(line)).max(0);
+            continued = line.trim_end().ends_with('\\');
+            commands.push(in_command);
+            continue;
+        }
+
+        let trimmed = line.trim();
+        continued = trimmed.ends_with('\\');
+        if let Some(name) = function_name(line) {
+            let rest = trimmed.split_once(name).map_or("", |(_, rest)| rest);
+            match rest.find('{') {
+                Some(index) => depth = (1 + nesting(&rest[index + 1..])).max(0),
+                // the body starts on the next line
+                None => continued = true,
+            }
+            in_command = false;
+        } else {
+            in_command = is_command(trimmed);
+            depth = nesting(trimmed).max(0);
+        }
+        commands.push(in_command);
+    }
+
+    commands
+}
+
+/// whether the first line of a statement runs a command, instead of defining something
+fn is_command(line: &str) -> bool {
+    let first = match words(line).next() {
+        Some(first) if !first.starts_with('#') => first,
+        _ => return false,
+    };
+    !(DEFINING_BUILTINS.contains(&first)
+        || SOURCING_BUILTINS.contains(&first)
+        || is_assignment(first) && !has_command_after_assignments(line))
+}
+
+/// `NAME=value` or `NAME+=value`
+fn is_assignment(word: &str) -> bool {
+    let name = match word.split_once('=') {
+        Some((name, _)) => name.strip_suffix('+').unwrap_or(name),
+        None => return false,
+    };
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `LANG=C sort file` runs `sort` with the variable set
+fn has_command_after_assignments(line: &str) -> bool {
+    words(line)
+        .find(|x| !is_assignment(x))
+        .is_some_and(|x| !x.starts_with('#') && !CLOSERS.contains(&x) && x != ";")
+}
+
+/// the number of blocks opened minus the number closed by the keywords in command position
+fn nesting(line: &str) -> i32 {
+    let mut change = 0;
+    let mut command_position = true;
+    for word in words(line) {
+        if word.starts_with('#') {
+            break;
+        }
+        if command_position {
+            if OPENERS.contains(&word) {
+                change += 1;
+            } else if CLOSERS.contains(&word) {
+                change -= 1;
+            }
+        }
+        command_position = COMMAND_SEPARATORS.contains(&word);
+    }
+    change
+}
+
+/// the words of the line, with `;` and `;;` split off as their own words
+fn words(line: &str) -> impl Iterator<Item = &str> {
+    split_words(line).into_iter().flat_map(|word| {
+        let (word, separator) = match word.strip_suffix(";;") {
+            Some(word) => (word, Some(";;")),
+            None => match word.strip_suffix(';') {
+                Some(word) => (word, Some(";")),
+                None => (word, None),
+            },
+        };
+        std::iter::once(word)
+            .filter(|x| !x.is_empty())
+            .chain(separator)
+    })
+}
+
+/// split on the whitespace outside of quotes and parentheses, so `NAME="a b"` and `$(cmd arg)` are one word
+fn split_words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut parens = 0;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => (),
+            (_, '\\') => escaped = true,
+            (Some(_), '"') => quote = None,
+            (Some(_), _) => (),
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '(') => parens += 1,
+            (None, ')') => parens -= 1,
+            (None, c) if c.is_whitespace() && parens <= 0 => {
+                if let Some(start) = start.take() {
+                    words.push(&line[start..index]);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        start.get_or_insert(index);
+    }
+    if let Some(start) = start {
+        words.push(&line[start..]);
+    }
+    words
+}
+
+#[cfg(test)]
+fn stripped(input: &str) -> Vec<&str> {
+    let lines: Vec<&str> = input.lines().collect();
+    lines
+        .iter()
+        .zip(toplevel_commands(&lines))
+        .filter(|(_, command)| *command)
+        .map(|(line, _)| *line)
+        .collect()
+}
+
+#[test]
+fn keeps_definitions() {
+    let input = r#"#!/usr/bin/env bash
+# helpers
+
+NAME="world"
+COUNT+=1
+export PATH="$HOME/bin:$PATH"
+readonly VERSION=1
+declare -a ITEMS=(a b)
+alias ll='ls -l'
+source ./other.sh
+. ./other.sh
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+function greet {
+    if [ -n "$1" ]; then
+        yell "hello $1"
+    fi
+}
+one_liner() { echo done; }
+brace_below()
+{
+    echo below
+}
+"#;
+    assert!(stripped(input).is_empty());
+}
+
+#[test]
+fn finds_toplevel_commands() {
+    let input = r#"yell() {
+    echo "$1"
+}
+yell "hallo"
+LANG=C sort file
+echo done
+"#;
+    assert_eq!(
+        vec![r#"yell "hallo""#, "LANG=C sort file", "echo done"],
+        stripped(input)
+    );
+}
+
+#[test]
+fn strips_whole_statements() {
+    let input = r#"if [ -n "$DEBUG" ]; then
+    set -x
+fi
+for name in a b; do
+    echo "$name"
+done
+cat <<EOF
+yell "in a heredoc"
+EOF
+echo "a string
+over two lines"
+run --long \
+    --option
+case "$1" in
+    start) echo start ;;
+esac
+{ echo grouped; }
+greet() {
+    echo greet
+}
+"#;
+    let commands = stripped(input);
+    assert_eq!(17, commands.len());
+    assert_eq!("esac", commands[15]);
+    assert_eq!("{ echo grouped; }", commands[16]);
+}
+
+#[test]
+fn nesting_keywords() {
+    assert_eq!(1, nesting("if [ -n \"$1\" ]; then"));
+    assert_eq!(0, nesting("if true; then echo; fi"));
+    assert_eq!(0, nesting("echo done if"));
+    assert_eq!(-1, nesting("done"));
+    assert_eq!(0, nesting("while read -r x; do echo \"$x\"; done < file"));
+    assert_eq!(0, nesting("# if in a comment"));
+    assert_eq!(0, nesting("result=$(if true; then echo; fi)"));
+}
+
+#[test]
+fn quoted_words() {
+    assert_eq!(
+        vec!["NAME=\"a b\"", "echo", "'c d'", "$(cmd arg)"],
+        split_words("NAME=\"a b\" echo 'c d' $(cmd arg)")
+    );
+    assert_eq!(vec!["a\\ b"], split_words("a\\ b"));
+    assert!(!is_command("NAME=\"a b c\""));
+    assert!(!is_command("ITEMS=(a b c)"));
+    assert!(is_command("LANG=C sort file"));
+    assert!(!is_command("NAME=value # comment"));
+}
@@ -0,0 +1,4 @@
+# import @utils/one_utils.sh
+# import @utils/one_more_utils.sh
+yell "hallo"
+print "hallo"
@@ -0,0 +1,2 @@
+source @utils/one_utils.sh
+yell "hallo"
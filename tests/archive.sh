@@ -0,0 +1,2 @@
+# import ./lib/common.sh
+common
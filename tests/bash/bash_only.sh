@@ -0,0 +1,4 @@
+greet() {
+    names=(hallo "$1")
+    echo "${names[*]}"
+}
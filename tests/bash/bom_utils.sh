@@ -0,0 +1,3 @@
+﻿print() {
+    echo "$1"
+}
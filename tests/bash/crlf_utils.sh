@@ -0,0 +1,3 @@
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
@@ -0,0 +1,9 @@
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+# demo
+if [ -n "$DEMO" ]; then
+    yell "demo"
+fi
+yell "hallo"
@@ -0,0 +1,3 @@
+b() {
+    echo "b"
+}
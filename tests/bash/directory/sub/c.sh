@@ -0,0 +1,3 @@
+c() {
+    echo "c"
+}
@@ -0,0 +1,14 @@
+# shared helpers, imported one region at a time
+
+# region greet
+# import ./one_utils.sh
+greet() {
+    yell "hello $1"
+}
+# endregion greet
+
+# region farewell
+farewell() {
+    echo "bye $1"
+}
+# endregion farewell
@@ -0,0 +1,2 @@
+source ./one_utils.sh
+source ./one_more_utils.sh
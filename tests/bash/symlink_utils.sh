@@ -0,0 +1 @@
+# import ./symlink_utils_link.sh
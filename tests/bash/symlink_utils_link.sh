@@ -0,0 +1 @@
+symlink_utils.sh
\ No newline at end of file
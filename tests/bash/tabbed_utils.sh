@@ -0,0 +1,8 @@
+tabbed() {
+	if true; then
+		echo "tab"
+	fi
+	cat <<EOF
+	kept
+EOF
+}
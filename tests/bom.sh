@@ -0,0 +1,2 @@
+# import ./bash/bom_utils.sh
+print "hallo"
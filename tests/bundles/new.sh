@@ -0,0 +1,8 @@
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    printf "%s\n" "$1"
+}
+yell "hallo"
+print "hallo"
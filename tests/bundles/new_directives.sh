@@ -0,0 +1,14 @@
+# import ./bash/one_utils.sh
+# (begin ./bash/one_utils.sh)
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+# (end ./bash/one_utils.sh)
+# import ./bash/one_more_utils.sh
+# (begin ./bash/one_more_utils.sh)
+print() {
+    printf "%s\n" "$1"
+}
+# (end ./bash/one_more_utils.sh)
+yell "hallo"
+print "hallo"
@@ -0,0 +1,8 @@
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
@@ -141,3 +141,1051 @@ fn file_or_config_required() {
 
     assert!(!out.status.success());
 }
+
+#[test]
+fn trailing_newline() {
+    let out = call_binary_to_string(["tests/no_newline.sh"]);
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+yell "hallo""#;
+
+    assert_eq!(expected, out)
+}
+
+#[test]
+fn no_shebang() {
+    let out = call_binary_to_string(["tests/shebang.sh"]);
+    assert!(out.starts_with("#!/bin/bash\n"));
+
+    let out = call_binary_to_string(["tests/shebang.sh", "--no-shebang"]);
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+yell "hallo"
+"#;
+
+    assert_eq!(expected, out)
+}
+
+#[test]
+fn diff() {
+    let out = call_binary_to_string(["diff", "tests/bundles/old.sh", "tests/bundles/new.sh"]);
+
+    let expected = r#"--- tests/bundles/old.sh
++++ tests/bundles/new.sh
+@@ -2,7 +2,7 @@
+     echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+ }
+ print() {
+-    echo "$1"
++    printf "%s\n" "$1"
+ }
+ yell "hallo"
+ print "hallo"
+"#;
+
+    assert_eq!(expected, out)
+}
+
+#[test]
+fn diff_attributed() {
+    let out = call_binary_to_string([
+        "diff",
+        "tests/bundles/old_directives.sh",
+        "tests/bundles/new_directives.sh",
+    ]);
+
+    assert!(out.contains("\n@@ -7,7 +7,7 @@ ./bash/one_more_utils.sh:2\n"));
+    assert!(out.contains("\n-    echo \"$1\"\n+    printf \"%s\\n\" \"$1\"\n"));
+}
+
+#[test]
+fn exclude() {
+    let out = call_binary_to_string(["tests/one.sh", "--exclude", "*/one_more_*.sh"]);
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+# import ./bash/one_more_utils.sh
+yell "hallo"
+print "hallo"
+"#;
+
+    assert_eq!(expected, out)
+}
+
+#[test]
+fn banner() {
+    let out = call_binary_to_string([
+        "tests/shebang.sh",
+        "--banner",
+        "generated by bash_bundler\n\n# do not edit",
+    ]);
+
+    let expected = r#"#!/bin/bash
+# generated by bash_bundler
+#
+# do not edit
+yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+yell "hallo"
+"#;
+
+    assert_eq!(expected, out)
+}
+
+#[test]
+fn squeeze_blank() {
+    let out = call_binary_to_string(["tests/heredoc.sh", "--squeeze-blank"]);
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+
+super_yell() {
+    yell "$1 !!!!!!"
+}
+cat <<EOF
+hallo
+
+
+EOF
+
+super_yell "hallo"
+"#;
+
+    assert_eq!(expected, out)
+}
+
+#[test]
+fn verbose() {
+    let out = call_binary(["tests/one.sh", "-vv"]);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    assert_eq!(call_binary_to_string(["tests/one.sh"]), stdout);
+    assert!(stderr.contains("import ./bash/one_utils.sh (comment) at tests/one.sh:1\n"));
+    assert!(stderr.contains("inline tests/./bash/one_more_utils.sh into tests/one.sh:2\n"));
+
+    // the dependents of an inlined file are loaded once, not again while inlining it
+    let out = call_binary(["tests/two.sh", "-v"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert_eq!(
+        1,
+        stderr.matches("load tests/./bash/./one_utils.sh\n").count()
+    );
+
+    let out = call_binary(["tests/one.sh"]);
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn env_in_import() {
+    let out = Command::new(BINARY)
+        .arg("tests/env.sh")
+        .env("BASH_BUNDLER_TEST_DIR", "./bash")
+        .output()
+        .expect("failed to execute process");
+
+    assert_eq!(
+        call_binary_to_string(["tests/one.sh"]),
+        String::from_utf8(out.stdout).unwrap()
+    );
+
+    let out = Command::new(BINARY)
+        .arg("tests/env.sh")
+        .env_remove("BASH_BUNDLER_TEST_DIR")
+        .output()
+        .expect("failed to execute process");
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("`BASH_BUNDLER_TEST_DIR`"));
+
+    let out = call_binary_to_string(["tests/env.sh", "--no-env"]);
+    assert!(out.starts_with("# import $BASH_BUNDLER_TEST_DIR/one_utils.sh\n"));
+}
+
+#[test]
+fn env_in_source() {
+    let out = Command::new(BINARY)
+        .args(["tests/env_source.sh", "--enable-source"])
+        .env("BASH_BUNDLER_TEST_DIR", "./bash")
+        .output()
+        .expect("failed to execute process");
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .starts_with("yell() {\n"));
+
+    let out = Command::new(BINARY)
+        .args(["tests/env_source.sh", "--enable-source"])
+        .env_remove("BASH_BUNDLER_TEST_DIR")
+        .output()
+        .expect("failed to execute process");
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .starts_with("source $BASH_BUNDLER_TEST_DIR/one_utils.sh\n"));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("an environment variable in the path is not set"));
+
+    let out = Command::new(BINARY)
+        .args(["tests/env_source.sh", "--enable-source", "--strict"])
+        .env_remove("BASH_BUNDLER_TEST_DIR")
+        .output()
+        .expect("failed to execute process");
+    assert!(!out.status.success());
+}
+
+#[test]
+fn flatten_once() {
+    let expected = r#"# import ./one_utils.sh
+# import ./two_empty.bash
+
+super_yell() {
+    yell "$1 !!!!!!"
+}
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+super_yell "hallo"
+"#;
+
+    assert_eq!(
+        expected,
+        call_binary_to_string(["tests/two.sh", "--flatten-once"])
+    );
+    assert_eq!(
+        expected,
+        call_binary_to_string(["tests/two.sh", "--depth", "1"])
+    );
+    assert_eq!(
+        call_binary_to_string(["tests/two.sh"]),
+        call_binary_to_string(["tests/two.sh", "--depth", "2"])
+    );
+}
+
+#[test]
+fn circular_error() {
+    let out = call_binary(["tests/circular.sh"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    assert!(!out.status.success());
+    assert!(stderr.starts_with("error: Circular import found\n"));
+    assert!(stderr.contains("| # import ./circular_"));
+    assert!(!stderr.contains('\x1b'));
+}
+
+#[test]
+fn json_error_format() {
+    let out = call_binary(["tests/circular.sh", "--error-format", "json"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    let cycle_start = std::fs::canonicalize("tests/bash/circular_1_utils.sh").unwrap();
+
+    assert_eq!(Some(3), out.status.code());
+    assert!(stderr.starts_with(
+        r#"{"kind":"circular","message":"Circular import found","path":"tests/./bash/./circular_2_utils.sh","line":1,"cycle":["#
+    ));
+    assert!(stderr.ends_with(&format!("\"{}\"]}}\n", cycle_start.display())));
+    assert_eq!(1, stderr.lines().count());
+}
+
+#[test]
+fn keep_going() {
+    let out = call_binary(["tests/keep_going.sh", "--strict", "--keep-going"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    // the exit code of the first error
+    assert_eq!(Some(2), out.status.code());
+    assert!(stderr
+        .starts_with("error: unresolved import tests/./bash/missing_utils.sh: file not found\n"));
+    assert!(stderr.contains("error: import tests/./bash/two_empty.bash is empty\n"));
+    assert!(stderr.contains(" --> tests/keep_going.sh:4\n"));
+    assert!(stderr.ends_with("error: aborting because of 3 errors\n"));
+
+    let out = call_binary([
+        "tests/keep_going.sh",
+        "--strict",
+        "--keep-going",
+        "--error-format",
+        "json",
+    ]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert_eq!(3, stderr.lines().count());
+    assert!(stderr.lines().all(|x| x.starts_with("{\"kind\":")));
+}
+
+#[test]
+fn strict_exit_code() {
+    let out = call_binary(["tests/unresolved.sh", "--strict"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    assert_eq!(Some(2), out.status.code());
+    assert!(stderr.starts_with("error: unresolved import "));
+}
+
+#[test]
+fn fail_on_warning() {
+    let out = call_binary(["tests/unresolved.sh"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .contains("# import ./bash/missing_utils.sh\n"));
+    assert!(stderr
+        .starts_with("warning: unresolved import tests/./bash/missing_utils.sh: file not found\n"));
+    assert!(stderr.contains(" --> tests/unresolved.sh:2\n"));
+
+    let out = call_binary(["tests/unresolved.sh", "--fail-on-warning"]);
+
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+}
+
+#[test]
+fn reindent() {
+    let out = call_binary_to_string([
+        "tests/tabbed.sh",
+        "--reindent",
+        "spaces",
+        "--indent-width",
+        "2",
+    ]);
+
+    let expected = "tabbed() {\n  if true; then\n    echo \"tab\"\n  fi\n  cat <<EOF\n\tkept\nEOF\n}\nyell() {\n    echo \"$1 !!!\" | tr '[:lower:]' '[:upper:]'\n}\ntabbed\n";
+
+    assert_eq!(expected, out);
+
+    let out = call_binary_to_string(["tests/tabbed.sh", "--reindent", "tabs"]);
+
+    assert!(out.contains("\n\techo \"$1 !!!\""));
+    assert!(out.contains("\n\t\techo \"tab\""));
+}
+
+#[test]
+fn output_from_config() {
+    let expected = call_binary_to_string(["tests/one.sh"]);
+
+    let out = call_binary(["--config", "tests/output_config.toml"]);
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+    assert_eq!(
+        expected,
+        std::fs::read_to_string("./target/output_config.sh").unwrap()
+    );
+
+    let out = call_binary([
+        "--config",
+        "tests/output_config.toml",
+        "--output",
+        "./target/output_override.sh",
+    ]);
+    assert!(out.status.success());
+    assert_eq!(
+        expected,
+        std::fs::read_to_string("./target/output_override.sh").unwrap()
+    );
+}
+
+#[test]
+fn config_profile() {
+    let out = call_binary_to_string(["--config", "tests/profile_config.toml"]);
+    assert_eq!(call_binary_to_string(["tests/one.sh"]), out);
+
+    let out = call_binary_to_string([
+        "--config",
+        "tests/profile_config.toml",
+        "--profile",
+        "debug",
+    ]);
+    assert_eq!(
+        call_binary_to_string(["tests/one.sh", "--disable-comment"]),
+        out
+    );
+
+    let out = call_binary([
+        "--config",
+        "tests/profile_config.toml",
+        "--profile",
+        "missing",
+    ]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("profile `missing` not found"));
+}
+
+#[test]
+fn shellcheck() {
+    let out = Command::new(BINARY)
+        .args(["tests/one.sh", "--shellcheck"])
+        .env("PATH", "")
+        .output()
+        .expect("failed to execute process");
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("`shellcheck` is not installed"));
+
+    let mock_dir = std::path::Path::new("./target/mock_shellcheck");
+    std::fs::create_dir_all(mock_dir).unwrap();
+    let mock = mock_dir.join("shellcheck");
+    std::fs::write(
+        &mock,
+        "#!/bin/sh\ncat > /dev/null\necho '-:2:5: error: mock error [SC1000]'\nexit 1\n",
+    )
+    .unwrap();
+    call_shell(&format!("chmod +x {}", mock.display()));
+
+    let path = format!(
+        "{}:{}",
+        mock_dir.display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let out = Command::new(BINARY)
+        .args(["tests/one.sh", "--shellcheck"])
+        .env("PATH", path)
+        .output()
+        .expect("failed to execute process");
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    assert!(!out.status.success());
+    assert!(stderr.contains("tests/./bash/one_utils.sh:2:5: error: mock error [SC1000]\n"));
+    assert!(stderr.contains("shellcheck found 1 error(s)"));
+}
+
+#[test]
+fn missing_path() {
+    let out = call_binary(["./typo.sh"]);
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("no such file: ./typo.sh"));
+}
+
+#[test]
+fn main_guard() {
+    let out = call_binary_to_string(["tests/guard.sh", "--main-guard"]);
+    std::fs::write("target/guard.sh", out).unwrap();
+
+    let executed = String::from_utf8(call_shell("bash target/guard.sh world").stdout).unwrap();
+    assert_eq!("HELLO WORLD !!!\ndone\n", executed);
+
+    let sourced = call_shell("bash -c 'source target/guard.sh && greet sourced'").stdout;
+    assert_eq!("HELLO SOURCED !!!\n", String::from_utf8(sourced).unwrap());
+}
+
+#[test]
+fn manifest() {
+    call_binary(["tests/two.sh", "--manifest", "target/manifest.txt"]);
+
+    let expected = "\
+5a1bf6341b97c48c0bff5dd5481968eb72acc1855bfe018eb568c988a3577a2f 26 tests/bash/one_more_utils.sh
+97bf5194fddfe300b3e77bac793b7181742dab81d20d4cd3e1516a4771f53c3a 58 tests/bash/one_utils.sh
+e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 0 tests/bash/two_empty.bash
+a8d156b8dab6aff523928e356149c9e94ff08ded1f6d1e467f55fa315af41769 89 tests/bash/two_utils.sh
+9715b7338bc67dd992e27fbe81ffb0a12069182a6e04004cb86061095ba8c981 109 tests/two.sh
+";
+    let manifest = std::fs::read_to_string("target/manifest.txt").unwrap();
+    assert_eq!(expected, manifest);
+}
+
+#[test]
+fn path_style() {
+    let current_dir = std::env::current_dir().unwrap();
+    call_binary([
+        "tests/two.sh",
+        "--manifest",
+        "target/absolute_manifest.txt",
+        "--path-style",
+        "absolute",
+    ]);
+
+    let manifest = std::fs::read_to_string("target/absolute_manifest.txt").unwrap();
+    let paths: Vec<_> = manifest
+        .lines()
+        .map(|x| x.rsplit(' ').next().unwrap())
+        .collect();
+    assert_eq!(5, paths.len());
+    assert_eq!(
+        current_dir.join("tests/bash/one_more_utils.sh").to_str(),
+        Some(paths[0])
+    );
+
+    let out = call_binary([
+        "tests/one.sh",
+        "--keep-import-markers",
+        "--path-style",
+        "relative",
+    ]);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("# (inlined tests/bash/one_utils.sh)\n"));
+}
+
+#[test]
+fn conditional_import() {
+    let out = Command::new(BINARY)
+        .arg("tests/conditional.sh")
+        .env("BUNDLE_OS", "linux")
+        .output()
+        .expect("failed to execute process");
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+# import ./bash/one_more_utils.sh if ${BUNDLE_OS} != linux
+yell "hallo"
+"#;
+    assert_eq!(expected, String::from_utf8(out.stdout).unwrap());
+
+    let out = Command::new(BINARY)
+        .arg("tests/conditional.sh")
+        .env_remove("BUNDLE_OS")
+        .output()
+        .expect("failed to execute process");
+
+    let expected = r#"# import ./bash/one_utils.sh if $BUNDLE_OS == linux
+print() {
+    echo "$1"
+}
+yell "hallo"
+"#;
+    assert_eq!(expected, String::from_utf8(out.stdout).unwrap());
+}
+
+#[test]
+fn timings() {
+    let out = call_binary(["tests/two.sh", "--timings"]);
+
+    assert!(out.status.success());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    let lines: Vec<_> = stderr.lines().collect();
+    assert!(lines[0].starts_with("load: "));
+    assert!(lines[1].starts_with("resolve: "));
+    assert_eq!("slowest reads:", lines[2]);
+    // the five files of the tree, the warning about the empty import follows
+    assert!(lines[3..8].iter().all(|x| x.starts_with("  ")));
+    assert!(lines[8].starts_with("warning: "));
+    assert!(stderr.contains(" tests/two.sh\n"));
+    assert_eq!(
+        call_binary_to_string(["tests/two.sh"]),
+        String::from_utf8(out.stdout).unwrap()
+    );
+}
+
+#[test]
+fn quiet() {
+    let out = call_binary([
+        "tests/unresolved.sh",
+        "--quiet",
+        "--stats",
+        "--output",
+        "target/quiet.sh",
+    ]);
+
+    assert!(out.status.success());
+    assert!(out.stderr.is_empty());
+    let written = std::fs::read_to_string("target/quiet.sh").unwrap();
+    assert!(written.ends_with("yell \"hallo\""));
+
+    let out = call_binary_to_string(["tests/one.sh", "--no-trailing-newline"]);
+    assert!(out.ends_with("print \"hallo\""));
+}
+
+#[test]
+fn list_styles() {
+    let out = call_binary_to_string(["tests/source.sh", "--list-styles"]);
+
+    let expected = "comment imports (`# import ./file.sh`): 0
+source imports (`source ./file.sh`): 3
+use `--enable-source --disable-comment`
+";
+    assert_eq!(expected, out);
+
+    // the flag is kept when the root file comes from the config
+    let out = call_binary_to_string(["--config", CONFIG_PATH, "--list-styles"]);
+    assert_eq!(expected, out);
+}
+
+#[test]
+fn bare_file_name() {
+    let binary = std::fs::canonicalize(BINARY).unwrap();
+    let out = Command::new(binary)
+        .arg("one.sh")
+        .current_dir("tests")
+        .output()
+        .expect("failed to execute process");
+
+    assert!(out.status.success());
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+print() {
+    echo "$1"
+}
+yell "hallo"
+print "hallo"
+"#;
+    assert_eq!(expected, String::from_utf8(out.stdout).unwrap());
+}
+
+#[test]
+fn check_unused() {
+    let out = call_binary_to_string(["check-unused", "tests/unused", "tests/unused/main.sh"]);
+
+    let expected = "tests/unused/lib/nested/also_unused.bash\ntests/unused/lib/unused.sh\n";
+    assert_eq!(expected, out);
+}
+
+#[test]
+fn run_bundle() {
+    let out = call_binary(["run", "tests/run.sh", "--", "hallo", "daar"]);
+
+    assert_eq!("hallo daar\n", String::from_utf8(out.stdout).unwrap());
+    assert_eq!("", String::from_utf8(out.stderr).unwrap());
+    assert_eq!(Some(3), out.status.code());
+
+    let out = call_binary(["run", "--interpreter", "sh", "tests/one.sh"]);
+
+    assert_eq!("HALLO !!!\nhallo\n", String::from_utf8(out.stdout).unwrap());
+    assert!(out.status.success());
+}
+
+#[test]
+fn archive() {
+    let expected = r#"common() {
+    more
+}
+more() {
+    echo "more"
+}
+common
+"#;
+
+    for archive in [
+        "tests/fragments.tar",
+        "tests/fragments.tar.gz",
+        "tests/fragments.zip",
+    ] {
+        let out = call_binary_to_string(["tests/archive.sh", "--archive", archive]);
+        assert_eq!(expected, out);
+    }
+}
+
+#[test]
+fn output_relative_to_root() {
+    let _ = std::fs::remove_file("target/relative_to_root.sh");
+    let out = call_binary([
+        "tests/one.sh",
+        "--output",
+        "../target/relative_to_root.sh",
+        "--output-relative-to",
+        "root",
+    ]);
+
+    assert!(out.status.success());
+    let written = std::fs::read_to_string("target/relative_to_root.sh").unwrap();
+    assert!(written.ends_with("print \"hallo\"\n"));
+}
+
+#[test]
+fn preprocess() {
+    let out = call_binary_to_string(["tests/one.sh", "--preprocess", "sed 's/hallo/hello/'"]);
+    assert!(out.ends_with("yell \"hello\"\nprint \"hello\"\n"));
+
+    let out = call_binary(["tests/one.sh", "--preprocess", "exit 3"]);
+    assert_eq!(Some(1), out.status.code());
+    assert_eq!(
+        "error: preprocessing tests/one.sh failed: `exit 3` exited with code 3\n",
+        String::from_utf8(out.stderr).unwrap()
+    );
+}
+
+#[test]
+fn only() {
+    let out = call_binary_to_string(["tests/two.sh", "--only", "tests/bash/two_utils.sh"]);
+    let expected = call_binary_to_string(["tests/bash/two_utils.sh"]);
+
+    assert_eq!(expected, out);
+    assert!(out.contains("yell()"));
+    assert!(!out.contains("print()"));
+
+    let out = call_binary([
+        "--config",
+        "tests/output_config.toml",
+        "--only",
+        "tests/bash/two_utils.sh",
+        "--output",
+        "target/only.sh",
+    ]);
+    assert!(out.status.success());
+    assert_eq!(expected, std::fs::read_to_string("target/only.sh").unwrap());
+}
+
+#[test]
+fn concurrency_safe_output() {
+    let children: Vec<_> = (0..4)
+        .map(|_| {
+            Command::new(BINARY)
+                .args([
+                    "tests/one.sh",
+                    "--output",
+                    "target/locked.sh",
+                    "--concurrency-safe-output",
+                ])
+                .spawn()
+                .expect("failed to execute process")
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+
+    let expected = call_binary_to_string(["tests/one.sh"]);
+    assert_eq!(
+        expected,
+        std::fs::read_to_string("target/locked.sh").unwrap()
+    );
+    assert!(std::path::Path::new("target/.locked.sh.lock").exists());
+}
+
+#[test]
+fn if_changed() {
+    let output = std::path::Path::new("target/if_changed.sh");
+    let _ = std::fs::remove_file(output);
+    let args = [
+        "tests/one.sh",
+        "--output",
+        "target/if_changed.sh",
+        "--if-changed",
+    ];
+
+    let out = Command::new(BINARY).args(args).output().unwrap();
+    assert!(out.status.success());
+    assert!(!String::from_utf8(out.stderr).unwrap().contains("unchanged"));
+    let modified = std::fs::metadata(output).unwrap().modified().unwrap();
+
+    let out = Command::new(BINARY).args(args).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        "unchanged target/if_changed.sh\n",
+        String::from_utf8(out.stderr).unwrap()
+    );
+    assert_eq!(
+        modified,
+        std::fs::metadata(output).unwrap().modified().unwrap()
+    );
+
+    std::fs::write(output, "echo old\n").unwrap();
+    let out = Command::new(BINARY).args(args).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        call_binary_to_string(["tests/one.sh"]),
+        std::fs::read_to_string(output).unwrap()
+    );
+}
+
+#[test]
+fn dry_run() {
+    let _ = std::fs::remove_file("target/dry_run.sh");
+    let _ = std::fs::remove_file("target/dry_run.manifest");
+    let size = call_binary_to_string(["tests/one.sh"]).len();
+
+    let out = call_binary_to_string(["tests/one.sh", "--dry-run"]);
+    assert_eq!(format!("would write {} bytes to stdout\n", size), out);
+
+    let out = call_binary_to_string([
+        "tests/one.sh",
+        "--dry-run",
+        "--output",
+        "target/dry_run.sh",
+        "--manifest",
+        "target/dry_run.manifest",
+    ]);
+    let lines: Vec<_> = out.lines().collect();
+    assert_eq!(2, lines.len());
+    assert!(lines[0].starts_with("would write "));
+    assert!(lines[0].ends_with(" bytes to target/dry_run.manifest"));
+    assert_eq!(
+        format!("would write {} bytes to target/dry_run.sh", size),
+        lines[1]
+    );
+    assert!(!std::path::Path::new("target/dry_run.sh").exists());
+    assert!(!std::path::Path::new("target/dry_run.manifest").exists());
+}
+
+#[test]
+fn validate() {
+    let out = call_binary(["validate", "tests/one.sh"]);
+
+    assert_eq!(Some(1), out.status.code());
+    assert_eq!(
+        "tests/one.sh:1: # import ./bash/one_utils.sh\ntests/one.sh:2: # import ./bash/one_more_utils.sh\n",
+        String::from_utf8(out.stdout).unwrap()
+    );
+    assert_eq!(
+        "error: found 2 import statement(s) left in the bundle\n",
+        String::from_utf8(out.stderr).unwrap()
+    );
+
+    call_binary([
+        "tests/one.sh",
+        "--keep-source-ref",
+        "--output",
+        "target/validate.sh",
+    ]);
+    let out = call_binary(["validate", "target/validate.sh"]);
+
+    assert!(out.status.success());
+    assert_eq!("", String::from_utf8(out.stdout).unwrap());
+}
+
+#[test]
+fn default_config() {
+    let binary = std::fs::canonicalize(BINARY).unwrap();
+    let out = Command::new(&binary)
+        .current_dir("tests/default_config")
+        .output()
+        .expect("failed to execute process");
+
+    assert_eq!("echo \"default\"\n", String::from_utf8(out.stdout).unwrap());
+
+    let out = Command::new(&binary)
+        .arg("../one.sh")
+        .current_dir("tests/default_config")
+        .output()
+        .expect("failed to execute process");
+
+    assert!(String::from_utf8(out.stdout).unwrap().contains("yell()"));
+
+    let out = Command::new(&binary)
+        .current_dir("tests/bash")
+        .output()
+        .expect("failed to execute process");
+
+    assert_eq!(Some(1), out.status.code());
+}
+
+#[test]
+fn exit_codes() {
+    let out = call_binary(["tests/unresolved.sh", "--fail-on-warning"]);
+    assert_eq!(Some(2), out.status.code());
+
+    let out = call_binary(["tests/circular.sh"]);
+    assert_eq!(Some(3), out.status.code());
+
+    let out = call_binary(["tests/one.sh", "--output", "target/missing_dir/out.sh"]);
+    assert_eq!(Some(4), out.status.code());
+
+    let out = call_binary([
+        "--config",
+        "tests/profile_config.toml",
+        "--profile",
+        "missing",
+    ]);
+    assert_eq!(Some(5), out.status.code());
+}
+
+#[test]
+fn comment_without_space() {
+    let out = call_binary_to_string(["tests/no_space.sh"]);
+
+    let expected = r#"yell() {
+    echo "$1 !!!" | tr '[:lower:]' '[:upper:]'
+}
+#important-note: not an import
+yell "hallo"
+"#;
+    assert_eq!(expected, out);
+}
+
+#[test]
+fn output_replaces_file() {
+    std::fs::create_dir_all("target/atomic").unwrap();
+    std::fs::write("target/atomic/out.sh", "old contents").unwrap();
+    call_shell("chmod +x target/atomic/out.sh");
+
+    let out = call_binary(["tests/one.sh", "--output", "target/atomic/out.sh"]);
+
+    assert!(out.status.success());
+    let written = std::fs::read_to_string("target/atomic/out.sh").unwrap();
+    assert!(written.starts_with("yell() {\n"));
+    let entries: Vec<_> = std::fs::read_dir("target/atomic").unwrap().collect();
+    assert_eq!(1, entries.len());
+    assert!(call_shell("test -x target/atomic/out.sh").status.success());
+}
+
+#[test]
+fn invalid_utf8() {
+    let out = call_binary(["tests/latin1.sh"]);
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("tests/./bash/latin1_utils.sh is not valid UTF-8 at byte 23"));
+
+    let out = call_binary_to_string(["tests/latin1.sh", "--lossy"]);
+    assert!(out.contains("    echo \"caf\u{FFFD}\"\n"));
+}
+
+#[test]
+fn ignore_case() {
+    let out = call_binary(["tests/ignore_case.sh", "--ignore-case"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout)
+        .unwrap()
+        .starts_with("yell() {\n"));
+    assert!(stderr.contains(
+        "import tests/./bash/One_Utils.sh only matches tests/./bash/one_utils.sh ignoring case"
+    ));
+
+    std::fs::create_dir_all("target/ignore_case").unwrap();
+    std::fs::write("target/ignore_case/main.sh", "# import ./UTILS.sh\n").unwrap();
+    std::fs::write("target/ignore_case/utils.sh", "").unwrap();
+    std::fs::write("target/ignore_case/Utils.sh", "").unwrap();
+
+    let out = call_binary(["target/ignore_case/main.sh", "--ignore-case"]);
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("matches multiple files ignoring case: target/ignore_case/Utils.sh, target/ignore_case/utils.sh"));
+}
+
+#[test]
+fn max_file_size() {
+    let out = Command::new(BINARY)
+        .args(["tests/one.sh", "--max-file-size", "80"])
+        .output()
+        .expect("failed to execute process");
+
+    assert_eq!(Some(1), out.status.code());
+    assert_eq!(
+        "error: tests/one.sh is larger than the limit of 80 bytes\n",
+        String::from_utf8(out.stderr).unwrap()
+    );
+
+    let out = call_binary_to_string(["tests/one.sh", "--max-file-size", "100"]);
+    assert_eq!(call_binary_to_string(["tests/one.sh"]), out);
+}
+
+#[test]
+fn max_output_bytes() {
+    let out = call_binary(["tests/two.sh", "--max-output-bytes", "50"]);
+
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("the output is larger than the limit of 50 bytes"));
+
+    let out = call_binary(["tests/two.sh", "--max-output-bytes", "10000"]);
+    assert!(out.status.success());
+}
+
+#[test]
+fn self_import_error() {
+    let out = call_binary(["tests/self_import.sh"]);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+
+    assert_eq!(Some(3), out.status.code());
+    assert!(stderr.starts_with("error: file imports itself: tests/self_import.sh:4\n"));
+    assert!(stderr.contains("4 | # import ./self_import.sh\n"));
+}
+
+#[test]
+fn require_readable() {
+    std::fs::create_dir_all("target/permissions").unwrap();
+    std::fs::write("target/permissions/main.sh", "# import ./secret.sh\n").unwrap();
+    std::fs::write("target/permissions/secret.sh", "echo secret\n").unwrap();
+    call_shell("chmod 000 target/permissions/secret.sh && chmod 644 target/permissions/main.sh");
+
+    let out = call_binary(["target/permissions/main.sh", "--require-readable"]);
+
+    assert_eq!(Some(4), out.status.code());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("error: target/permissions/./secret.sh is not readable"));
+
+    let out = call_binary(["target/permissions/main.sh", "--require-executable"]);
+
+    assert_eq!(Some(4), out.status.code());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("error: target/permissions/main.sh is not executable"));
+}
+
+#[test]
+fn config_extends() {
+    let out = call_binary_to_string(["--config", "tests/extends_config.toml"]);
+    assert!(out.starts_with("# (inlined ./bash/two_utils.sh)\n# (inlined ./one_utils.sh)\n"));
+
+    let out = call_binary_to_string([
+        "--config",
+        "tests/extends_config.toml",
+        "--profile",
+        "plain",
+    ]);
+    assert!(out.starts_with("yell() {\n"));
+    assert!(out.ends_with("super_yell \"hallo\"\n"));
+
+    let out = call_binary(["--config", "tests/cycle_config.toml"]);
+    assert_eq!(Some(5), out.status.code());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("config tests/./cycle_config.toml extends itself"));
+}
+
+#[test]
+fn config_aliases() {
+    let out = call_binary_to_string(["--config", "tests/alias_config.toml"]);
+    assert_eq!(call_binary_to_string(["tests/one.sh"]), out);
+
+    let out = call_binary_to_string([
+        "tests/alias_source.sh",
+        "--enable-source",
+        "--config",
+        "tests/alias_config.toml",
+    ]);
+    assert!(out.starts_with("yell() {\n"));
+    assert!(out.ends_with("}\nyell \"hallo\"\n"));
+
+    let out = call_binary(["tests/alias.sh"]);
+    assert_eq!(Some(1), out.status.code());
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("alias `@utils` used in import is not in the `[bundler.aliases]` table"));
+}
+
+#[test]
+fn ignore_file() {
+    let binary = std::fs::canonicalize(BINARY).unwrap();
+    let expected = "a() {\n    echo a\n}\nkeep() {\n    echo keep\n}\nmain\n";
+
+    for args in [&["glob.sh"][..], &["directory.sh", "--recursive-dir"]] {
+        let out = Command::new(&binary)
+            .args(args)
+            .current_dir("tests/ignore")
+            .output()
+            .expect("failed to execute process");
+
+        assert_eq!(expected, String::from_utf8(out.stdout).unwrap());
+    }
+
+    // the ignore file is read from the directory of the root file
+    let out = call_binary_to_string(["tests/ignore/glob.sh"]);
+    assert_eq!(expected, out);
+}
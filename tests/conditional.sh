@@ -0,0 +1,3 @@
+# import ./bash/one_utils.sh if $BUNDLE_OS == linux
+# import ./bash/one_more_utils.sh if ${BUNDLE_OS} != linux
+yell "hallo"
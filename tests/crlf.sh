@@ -0,0 +1,2 @@
+# import ./bash/crlf_utils.sh
+yell "hallo"
@@ -0,0 +1,2 @@
+# import ./bash/directory/
+a
@@ -0,0 +1,4 @@
+. ./bash/one_utils.sh
+./bash/one_more_utils.sh
+.hidden
+yell "hallo"
@@ -0,0 +1,4 @@
+# import $BASH_BUNDLER_TEST_DIR/one_utils.sh
+# import ${BASH_BUNDLER_TEST_DIR}/one_more_utils.sh
+yell "hallo"
+print "hallo"
@@ -0,0 +1,2 @@
+source $BASH_BUNDLER_TEST_DIR/one_utils.sh
+yell "hallo"
@@ -0,0 +1,10 @@
+#!/usr/bin/env bash
+# import ./bash/one_utils.sh
+
+greet() {
+    yell "hello $1"
+}
+
+# entry point
+greet "$1"
+echo done
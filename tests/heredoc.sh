@@ -0,0 +1,9 @@
+# import ./bash/two_utils.sh
+cat <<EOF
+hallo
+
+
+EOF
+
+
+super_yell "hallo"
@@ -0,0 +1,2 @@
+# import ./lib/**/*.sh
+main
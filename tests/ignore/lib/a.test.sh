@@ -0,0 +1,3 @@
+a_test() {
+    a
+}
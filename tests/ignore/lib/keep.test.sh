@@ -0,0 +1,3 @@
+keep() {
+    echo keep
+}
@@ -0,0 +1,3 @@
+helper() {
+    echo helper
+}
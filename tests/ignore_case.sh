@@ -0,0 +1,2 @@
+# import ./bash/One_Utils.sh
+yell "hallo"
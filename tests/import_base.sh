@@ -0,0 +1,4 @@
+# import ./one_utils.sh
+# import ./one_more_utils.sh
+yell "hallo"
+print "hallo"
@@ -0,0 +1,2 @@
+# import ../bash/one_more_utils.sh
+print "done"
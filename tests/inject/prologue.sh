@@ -0,0 +1 @@
+set -euo pipefail
@@ -0,0 +1,5 @@
+# import ./bash/missing_utils.sh
+# import ./bash/two_empty.bash
+# import ./bash/one_utils.sh
+# import ./bash/other_missing_utils.sh
+yell "hallo"
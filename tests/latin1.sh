@@ -0,0 +1,2 @@
+# import ./bash/latin1_utils.sh
+greet
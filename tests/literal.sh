@@ -0,0 +1,8 @@
+# import ./bash/one_utils.sh
+cat <<EOF
+# import ./bash/one_more_utils.sh
+EOF
+msg="
+# import ./bash/one_more_utils.sh
+"
+yell "$msg"
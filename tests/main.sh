@@ -0,0 +1,5 @@
+# import ./bash/one_utils.sh
+
+main() {
+    yell "$1"
+}
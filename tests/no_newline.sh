@@ -0,0 +1,2 @@
+# import ./bash/one_utils.sh
+yell "hallo"
\ No newline at end of file
@@ -0,0 +1,3 @@
+#import ./bash/one_utils.sh
+#important-note: not an import
+yell "hallo"
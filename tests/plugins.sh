@@ -0,0 +1,4 @@
+# import ./plugins/**/*.sh
+plugin_a
+plugin_b
+plugin_c
@@ -0,0 +1,3 @@
+plugin_a() {
+    echo a
+}
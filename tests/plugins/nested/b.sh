@@ -0,0 +1,3 @@
+plugin_b() {
+    echo b
+}
@@ -0,0 +1,3 @@
+plugin_c() {
+    echo c
+}
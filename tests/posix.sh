@@ -0,0 +1,5 @@
+#!/bin/sh
+# import ./bash/bash_only.sh
+if [[ -n "$1" ]]; then
+    greet "$1"
+fi
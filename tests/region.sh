@@ -0,0 +1,3 @@
+#!/usr/bin/env bash
+# import ./bash/regions.sh#greet
+greet "world"
@@ -0,0 +1,3 @@
+# import ./bash/one_more_utils.sh
+print "$1 $2"
+exit 3
@@ -0,0 +1,5 @@
+yell() {
+    echo "$1"
+}
+# import ./self_import.sh
+yell "hallo"
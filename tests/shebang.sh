@@ -0,0 +1,3 @@
+#!/bin/bash
+# import ./bash/one_utils.sh
+yell "hallo"
@@ -0,0 +1,4 @@
+source ./bash/one_utils.sh || exit 1
+source ./bash/one_more_utils.sh && echo "loaded"
+yell "hallo"
+print "hallo"
@@ -0,0 +1,5 @@
+source "./bash/one_utils.sh"
+source './bash/one_more_utils.sh'
+source "./bash/one_utils.sh
+yell "hallo"
+print "hallo"
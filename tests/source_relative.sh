@@ -0,0 +1,3 @@
+source ./bash/source_relative_utils.sh
+yell "hallo"
+print "hallo"
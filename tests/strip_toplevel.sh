@@ -0,0 +1,3 @@
+#!/usr/bin/env bash
+# import ./bash/demo_utils.sh
+yell "world"
@@ -0,0 +1 @@
+# import ./bash/symlink_utils.sh
@@ -0,0 +1,3 @@
+# import ./bash/tabbed_utils.sh
+# import ./bash/one_utils.sh
+tabbed
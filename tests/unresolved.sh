@@ -0,0 +1,3 @@
+# import ./bash/one_utils.sh
+# import ./bash/missing_utils.sh
+yell "hallo"
@@ -0,0 +1,3 @@
+also_unused() {
+    echo also unused
+}
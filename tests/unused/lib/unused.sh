@@ -0,0 +1,3 @@
+unused() {
+    echo unused
+}
@@ -0,0 +1,3 @@
+used() {
+    echo used
+}
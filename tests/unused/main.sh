@@ -0,0 +1,2 @@
+# import ./lib/used.sh
+used